@@ -0,0 +1,338 @@
+//! Threshold alarms.
+//!
+//! A rule such as `> 90% for 10s` fires once the load has stayed past its
+//! threshold for the hold time, and recovers once the load is back on the
+//! other side of the threshold by the hysteresis margin, so a load hovering
+//! around the line does not fire it over and over. Evaluation runs on the
+//! caller's clock and never touches the terminal.
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+const MILLIS_PER_SEC: f64 = 1000.0;
+const SECS_PER_MIN: f64 = 60.0;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Above,
+    Below,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct AlarmRule {
+    pub comparison: Comparison,
+    /// Load as a fraction.
+    pub threshold: f32,
+    /// How long the threshold must stay crossed before the rule fires.
+    pub hold: Duration,
+}
+
+impl AlarmRule {
+    /// Parses `[load] > 90% [for 10s]`. The hold time takes an `ms`, `s` or
+    /// `m` suffix and is in seconds without one.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        let rest = text.strip_prefix("load").unwrap_or(text).trim_start();
+        let (comparison, rest) = if let Some(rest) = rest.strip_prefix('>') {
+            (Comparison::Above, rest)
+        } else if let Some(rest) = rest.strip_prefix('<') {
+            (Comparison::Below, rest)
+        } else {
+            return Err("expected '>' or '<'".to_string());
+        };
+        let (threshold, hold) = match rest.split_once("for") {
+            Some((threshold, hold)) => (threshold, Some(hold)),
+            None => (rest, None),
+        };
+        let threshold = threshold
+            .trim()
+            .trim_end_matches('%')
+            .parse()
+            .ok()
+            .filter(|percent: &f32| (0.0..=100.0).contains(percent))
+            .ok_or_else(|| format!("threshold '{}' must be between 0 and 100", threshold.trim()))?;
+        let hold = match hold {
+            Some(hold) => parse_hold(hold.trim()).ok_or_else(|| {
+                format!(
+                    "hold time '{}' must be a duration such as 10s, 2m or 500ms",
+                    hold.trim()
+                )
+            })?,
+            None => Duration::ZERO,
+        };
+        Ok(Self {
+            comparison,
+            threshold: threshold / 100.0,
+            hold,
+        })
+    }
+
+    fn breached(&self, load: f32) -> bool {
+        match self.comparison {
+            Comparison::Above => load > self.threshold,
+            Comparison::Below => load < self.threshold,
+        }
+    }
+
+    fn cleared(&self, load: f32, hysteresis: f32) -> bool {
+        match self.comparison {
+            Comparison::Above => load < self.threshold - hysteresis,
+            Comparison::Below => load > self.threshold + hysteresis,
+        }
+    }
+}
+
+impl fmt::Display for AlarmRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = match self.comparison {
+            Comparison::Above => '>',
+            Comparison::Below => '<',
+        };
+        // Rounded to a tenth so 90% does not come back as 89.99999%.
+        let percent = (self.threshold * 1000.0).round() / 10.0;
+        write!(f, "load {sign} {percent}%")?;
+        if !self.hold.is_zero() {
+            write!(f, " for {}s", self.hold.as_secs_f64())?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_hold(text: &str) -> Option<Duration> {
+    let (number, scale) = if let Some(number) = text.strip_suffix("ms") {
+        (number, 1.0 / MILLIS_PER_SEC)
+    } else if let Some(number) = text.strip_suffix('s') {
+        (number, 1.0)
+    } else if let Some(number) = text.strip_suffix('m') {
+        (number, SECS_PER_MIN)
+    } else {
+        (text, 1.0)
+    };
+    let secs: f64 = number.trim().parse().ok()?;
+    Duration::try_from_secs_f64(secs * scale).ok()
+}
+
+/// Parses a comma-separated list of rules; `off` gives none.
+pub fn parse_rules(text: &str) -> Result<Vec<AlarmRule>, String> {
+    if text.trim() == "off" {
+        return Ok(Vec::new());
+    }
+    let rules: Vec<&str> = text.split(',').collect();
+    rules
+        .iter()
+        .map(|rule| match AlarmRule::parse(rule) {
+            Err(err) if rules.len() > 1 => Err(format!("'{}': {err}", rule.trim())),
+            result => result,
+        })
+        .collect()
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum AlarmEvent {
+    Fired,
+    Recovered,
+}
+
+/// A rule firing or recovering.
+pub struct Change {
+    pub rule: AlarmRule,
+    pub event: AlarmEvent,
+    pub load: f32,
+    /// How long the threshold had been crossed.
+    pub duration: Duration,
+}
+
+/// The last rule to fire, shown until it is acknowledged.
+pub struct Pending {
+    pub rule: AlarmRule,
+    pub recovered: bool,
+    index: usize,
+}
+
+struct RuleState {
+    rule: AlarmRule,
+    /// When the threshold was first crossed in the current run.
+    breached_since: Option<Instant>,
+    active: bool,
+}
+
+pub struct Alarms {
+    rules: Vec<RuleState>,
+    /// Margin, as a fraction of load, the load must move back past the
+    /// threshold by before an active rule recovers.
+    hysteresis: f32,
+    pending: Option<Pending>,
+}
+
+impl Alarms {
+    pub fn new(rules: &[AlarmRule], hysteresis: f32) -> Self {
+        Self {
+            rules: rules
+                .iter()
+                .map(|&rule| RuleState {
+                    rule,
+                    breached_since: None,
+                    active: false,
+                })
+                .collect(),
+            hysteresis,
+            pending: None,
+        }
+    }
+
+    /// Feeds the load at `now` through every rule and returns the rules that
+    /// fired or recovered.
+    pub fn update(&mut self, load: f32, now: Instant) -> Vec<Change> {
+        let mut changes = Vec::new();
+        for (index, state) in self.rules.iter_mut().enumerate() {
+            if state.active {
+                if !state.rule.cleared(load, self.hysteresis) {
+                    continue;
+                }
+                let since = state.breached_since.take().unwrap_or(now);
+                state.active = false;
+                changes.push(Change {
+                    rule: state.rule,
+                    event: AlarmEvent::Recovered,
+                    load,
+                    duration: now.duration_since(since),
+                });
+                if let Some(pending) = &mut self.pending
+                    && pending.index == index
+                {
+                    pending.recovered = true;
+                }
+            } else if state.rule.breached(load) {
+                let since = *state.breached_since.get_or_insert(now);
+                let duration = now.duration_since(since);
+                if duration < state.rule.hold {
+                    continue;
+                }
+                state.active = true;
+                changes.push(Change {
+                    rule: state.rule,
+                    event: AlarmEvent::Fired,
+                    load,
+                    duration,
+                });
+                self.pending = Some(Pending {
+                    rule: state.rule,
+                    recovered: false,
+                    index,
+                });
+            } else {
+                state.breached_since = None;
+            }
+        }
+        changes
+    }
+
+    pub fn pending(&self) -> Option<&Pending> {
+        self.pending.as_ref()
+    }
+
+    /// Dismisses the banner. Active rules stay active and fire again only
+    /// after recovering.
+    pub fn acknowledge(&mut self) {
+        self.pending = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HYSTERESIS: f32 = 0.05;
+
+    fn rule(text: &str) -> AlarmRule {
+        AlarmRule::parse(text).unwrap()
+    }
+
+    /// Feeds `loads` one second apart from `start` and returns the events.
+    fn feed(alarms: &mut Alarms, start: Instant, loads: &[f32]) -> Vec<AlarmEvent> {
+        loads
+            .iter()
+            .enumerate()
+            .flat_map(|(secs, &load)| alarms.update(load, start + Duration::from_secs(secs as u64)))
+            .map(|change| change.event)
+            .collect()
+    }
+
+    #[test]
+    fn parses_rules() {
+        let parsed = rule("load > 90% for 10s");
+        assert!(parsed.comparison == Comparison::Above);
+        assert!((parsed.threshold - 0.9).abs() < 1e-6);
+        assert_eq!(parsed.hold, Duration::from_secs(10));
+        assert_eq!(rule("<5 for 1m").hold, Duration::from_secs(60));
+        assert_eq!(rule("> 50% for 500ms").hold, Duration::from_millis(500));
+        assert_eq!(rule("> 50%").hold, Duration::ZERO);
+        assert_eq!(rule("> 90% for 10s").to_string(), "load > 90% for 10s");
+        assert_eq!(parse_rules("> 90%, < 5% for 1m").unwrap().len(), 2);
+        assert!(parse_rules("off").unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_bad_rules() {
+        assert_eq!(parse_rules("= 90%").err().unwrap(), "expected '>' or '<'");
+        assert_eq!(
+            parse_rules("> 120%").err().unwrap(),
+            "threshold '120%' must be between 0 and 100"
+        );
+        assert_eq!(
+            parse_rules("> 90% for soon").err().unwrap(),
+            "hold time 'soon' must be a duration such as 10s, 2m or 500ms"
+        );
+        assert_eq!(
+            parse_rules("> 90%, < x").err().unwrap(),
+            "'< x': threshold 'x' must be between 0 and 100"
+        );
+    }
+
+    #[test]
+    fn fires_after_hold_time() {
+        let mut alarms = Alarms::new(&[rule("> 90% for 3s")], HYSTERESIS);
+        let start = Instant::now();
+        // A dip resets the hold time.
+        assert!(feed(&mut alarms, start, &[0.95, 0.95, 0.5, 0.95, 0.95]).is_empty());
+        let fired = alarms.update(0.95, start + Duration::from_secs(8));
+        assert_eq!(fired.len(), 1);
+        assert!(fired[0].event == AlarmEvent::Fired);
+        assert_eq!(fired[0].duration, Duration::from_secs(5));
+        assert!(alarms.pending().is_some_and(|pending| !pending.recovered));
+    }
+
+    #[test]
+    fn recovers_past_hysteresis() {
+        let mut alarms = Alarms::new(&[rule("> 90%")], HYSTERESIS);
+        let events = feed(&mut alarms, Instant::now(), &[0.95, 0.88, 0.86, 0.84]);
+        // 0.88 and 0.86 are below the line but inside the margin.
+        assert!(events == [AlarmEvent::Fired, AlarmEvent::Recovered]);
+        assert!(alarms.pending().is_some_and(|pending| pending.recovered));
+    }
+
+    #[test]
+    fn fires_again_only_after_recovering() {
+        let mut alarms = Alarms::new(&[rule("< 10%")], HYSTERESIS);
+        let events = feed(&mut alarms, Instant::now(), &[0.05, 0.12, 0.05, 0.2, 0.05]);
+        assert!(events == [AlarmEvent::Fired, AlarmEvent::Recovered, AlarmEvent::Fired]);
+    }
+
+    #[test]
+    fn acknowledge_clears_banner_but_not_rule() {
+        let mut alarms = Alarms::new(&[rule("> 90%")], HYSTERESIS);
+        let start = Instant::now();
+        assert!(feed(&mut alarms, start, &[0.95]) == [AlarmEvent::Fired]);
+        alarms.acknowledge();
+        assert!(alarms.pending().is_none());
+        // Still active, so staying high fires nothing new.
+        assert!(alarms
+            .update(0.99, start + Duration::from_secs(1))
+            .is_empty());
+        assert!(alarms.pending().is_none());
+        // Recovering after acknowledge brings no banner back.
+        let recovered = alarms.update(0.5, start + Duration::from_secs(2));
+        assert!(recovered[0].event == AlarmEvent::Recovered);
+        assert!(alarms.pending().is_none());
+    }
+}
@@ -0,0 +1,228 @@
+//! PQRST heartbeat synthesis.
+//!
+//! A beat is the sum of five Gaussian bumps (P, Q, R, S and T waves) placed at
+//! fixed offsets from the start of the beat. The heart rate is derived from the
+//! load, and the template is compressed when a beat gets shorter than the
+//! complex itself so the T wave never spills into the next beat.
+
+const BPM_MIN: f32 = 50.0;
+const BPM_MAX: f32 = 150.0;
+const SECS_PER_MIN: f32 = 60.0;
+const WAVE_TAIL_WIDTHS: f32 = 3.0;
+/// Heart rate during tachycardia, as a multiple of the rate at full load.
+const TACHYCARDIA_RATE: f32 = 1.25;
+/// Fibrillation waves as (frequency in Hz, amplitude, phase offset). The
+/// frequencies share no common period, so the sum never settles into a rhythm.
+const FIBRILLATION_WAVES: [(f32, f32, f32); 3] =
+    [(4.1, 0.25, 0.0), (6.7, 0.15, 1.3), (9.3, 0.10, 0.4)];
+
+/// One deflection of the beat: a bump centred `center` seconds after the beat
+/// starts, lasting roughly `width` seconds either side of its peak.
+#[derive(Clone, Copy)]
+pub struct Wave {
+    pub center: f32,
+    pub width: f32,
+    pub amplitude: f32,
+}
+
+impl Wave {
+    const fn new(center: f32, width: f32, amplitude: f32) -> Self {
+        Self {
+            center,
+            width,
+            amplitude,
+        }
+    }
+
+    /// Parses `center,width,amplitude`, with the times in seconds and the
+    /// amplitude in the `-1..=1` signal range.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let numbers: Vec<f32> = text
+            .split(',')
+            .map(|part| part.trim().parse())
+            .collect::<Result<_, _>>()
+            .map_err(|_| "expected CENTER,WIDTH,AMPLITUDE".to_string())?;
+        let [center, width, amplitude] = numbers[..] else {
+            return Err("expected CENTER,WIDTH,AMPLITUDE".to_string());
+        };
+        if !(center.is_finite() && width.is_finite() && amplitude.is_finite()) {
+            return Err("values must be finite numbers".to_string());
+        }
+        if center < 0.0 {
+            return Err("center must not be negative".to_string());
+        }
+        if width <= 0.0 {
+            return Err("width must be positive".to_string());
+        }
+        if !(-1.0..=1.0).contains(&amplitude) {
+            return Err("amplitude must be between -1 and 1".to_string());
+        }
+        Ok(Self::new(center, width, amplitude))
+    }
+
+    fn value(&self, t: f32) -> f32 {
+        if self.width <= 0.0 {
+            return 0.0;
+        }
+        let d = (t - self.center) / self.width;
+        self.amplitude * (-0.5 * d * d).exp()
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct BeatTemplate {
+    pub p: Wave,
+    pub q: Wave,
+    pub r: Wave,
+    pub s: Wave,
+    pub t: Wave,
+    pub bpm_min: f32,
+    pub bpm_max: f32,
+}
+
+impl Default for BeatTemplate {
+    fn default() -> Self {
+        Self {
+            p: Wave::new(0.12, 0.040, 0.15),
+            q: Wave::new(0.26, 0.020, -0.15),
+            r: Wave::new(0.30, 0.022, 0.90),
+            s: Wave::new(0.34, 0.022, -0.30),
+            t: Wave::new(0.58, 0.060, 0.30),
+            bpm_min: BPM_MIN,
+            bpm_max: BPM_MAX,
+        }
+    }
+}
+
+impl BeatTemplate {
+    /// Checks that the waves keep their P, Q, R, S, T order.
+    pub fn validate(&self) -> Result<(), String> {
+        let waves = [
+            ("p", self.p),
+            ("q", self.q),
+            ("r", self.r),
+            ("s", self.s),
+            ("t", self.t),
+        ];
+        for pair in waves.windows(2) {
+            let ((first, before), (second, after)) = (pair[0], pair[1]);
+            if before.center >= after.center {
+                return Err(format!(
+                    "{first}-wave center ({}) must come before {second}-wave center ({})",
+                    before.center, after.center
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn bpm(&self, load: f32) -> f32 {
+        self.bpm_min + load.clamp(0.0, 1.0) * (self.bpm_max - self.bpm_min)
+    }
+
+    /// The same complex at a fixed rate above the normal range.
+    pub fn tachycardia(&self) -> Self {
+        let bpm = self.bpm_max * TACHYCARDIA_RATE;
+        Self {
+            bpm_min: bpm,
+            bpm_max: bpm,
+            ..*self
+        }
+    }
+
+    /// Time from the start of the beat until the T wave has faded out.
+    fn span(&self) -> f32 {
+        self.t.center + WAVE_TAIL_WIDTHS * self.t.width
+    }
+
+    /// How much the template is squeezed to fit a beat lasting `period`.
+    fn scale(&self, period: f32) -> f32 {
+        let span = self.span();
+        if span > 0.0 {
+            (period / span).min(1.0)
+        } else {
+            1.0
+        }
+    }
+
+    fn sample(&self, t: f32, period: f32) -> f32 {
+        let t = t / self.scale(period);
+        [self.p, self.q, self.r, self.s, self.t]
+            .iter()
+            .map(|wave| wave.value(t))
+            .sum()
+    }
+}
+
+/// Running position inside the current beat.
+pub struct Beat {
+    elapsed: f32,
+    bpm: f32,
+    /// Set by the step nearest the R peak, until [`Beat::take_peak`].
+    peaked: bool,
+}
+
+impl Beat {
+    pub fn new() -> Self {
+        Self {
+            elapsed: 0.0,
+            bpm: BPM_MIN,
+            peaked: false,
+        }
+    }
+
+    pub fn bpm(&self) -> f32 {
+        self.bpm
+    }
+
+    /// Fraction of the current beat that has elapsed, in `0.0..1.0`.
+    pub fn progress(&self) -> f32 {
+        self.elapsed / self.period()
+    }
+
+    fn period(&self) -> f32 {
+        SECS_PER_MIN / self.bpm.max(1.0)
+    }
+
+    /// True once per beat, after the step whose value is nearest the R peak.
+    pub fn take_peak(&mut self) -> bool {
+        std::mem::take(&mut self.peaked)
+    }
+
+    /// Jumps to just before the QRS complex, skipping the P wave like an
+    /// ectopic beat would. Ignored until the S wave of the current beat has
+    /// passed, so a beat is never interrupted mid-complex.
+    pub fn premature(&mut self, template: &BeatTemplate) {
+        let scale = template.scale(self.period());
+        let s_end = (template.s.center + WAVE_TAIL_WIDTHS * template.s.width) * scale;
+        if self.elapsed >= s_end {
+            self.elapsed = (template.q.center - WAVE_TAIL_WIDTHS * template.q.width) * scale;
+        }
+    }
+
+    /// Returns the value at the current position and moves `dt` seconds on. The
+    /// rate is only picked up when a new beat starts so a complex is never cut
+    /// short halfway through.
+    pub fn step(&mut self, template: &BeatTemplate, load: f32, dt: f32) -> f32 {
+        let value = template.sample(self.elapsed, self.period());
+        let peak = template.r.center * template.scale(self.period());
+        if (peak - dt / 2.0..peak + dt / 2.0).contains(&self.elapsed) {
+            self.peaked = true;
+        }
+        self.elapsed += dt;
+        if self.elapsed >= self.period() {
+            self.elapsed -= self.period();
+            self.bpm = template.bpm(load);
+            self.elapsed = self.elapsed.min(self.period());
+        }
+        value
+    }
+}
+
+/// Chaotic baseline with no complexes, `t` seconds into fibrillation.
+pub fn fibrillation(t: f32) -> f32 {
+    FIBRILLATION_WAVES
+        .iter()
+        .map(|&(hz, amplitude, offset)| amplitude * (std::f32::consts::TAU * hz * t + offset).sin())
+        .sum()
+}
@@ -0,0 +1,249 @@
+//! Command-line parsing.
+
+use crate::settings::{SetError, Settings};
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub const USAGE: &str = "\
+Usage: ecg-cpu [OPTIONS]
+
+Options:
+      --config <PATH>         Config file (default: ~/.config/ecg-cpu/config.toml)
+      --record <FILE>         Write every sample to FILE (.csv, .jsonl, .ndjson,
+                              .edf or .hea)
+      --replay <FILE>         Play back a recording or a file of one number per line
+      --snapshot              Sample for a while, print the last frame and exit
+      --width <COLS>          Snapshot width (default: terminal width or 80)
+      --height <ROWS>         Snapshot height (default: terminal height or 24)
+      --duration <SECS>       How long a snapshot samples for (default: 5)
+      --fps <N>               Initial frames per second
+      --fps-min <N>           Lowest FPS reachable with -
+      --fps-max <N>           Highest FPS reachable with +
+      --interval <MS>         Time between readings (default: 250)
+      --warn <PERCENT>        Load at which the trace turns yellow
+      --crit <PERCENT>        Load at which the trace turns red
+      --grid <ROWSxCOLS|off>  Background grid spacing, e.g. 4x6
+      --source <NAME>         Signal to monitor: cpu, cores, memory, swap, load,
+                              net, disk, psi, cgroup, process
+      --cgroup <PATH>         cgroup v2 directory for the cgroup source
+                              (default: this process's group)
+      --pid <PID>             Watch this process (implies --source process)
+      --name <PATTERN>        Watch processes whose name contains PATTERN
+      --user <USER>           Watch processes owned by USER (name or uid)
+      --model <NAME>          Waveform model: pqrst, sine
+      --renderer <NAME>       Trace renderer: braille, ascii (default: from locale)
+      --display <NAME>        Display mode: scroll, sweep
+      --plot <NAME>           What to plot: synthetic, raw, combined
+      --erase-gap <CELLS>     Blank gap ahead of the sweep write head
+      --pulse-threshold <PERCENT>
+                              Load above which the sine model pulses
+      --bpm-min <BPM>         Heart rate at idle
+      --bpm-max <BPM>         Heart rate at full load
+      --p-wave <CENTER,WIDTH,AMPLITUDE>
+                              P wave of the beat, times in seconds; likewise
+                              --q-wave, --r-wave, --s-wave and --t-wave
+      --alarm <RULES>         Alarm rules, e.g. \"> 90% for 10s, < 5% for 1m\"
+      --alarm-hysteresis <PERCENT>
+                              Margin before an alarm recovers (default: 5)
+      --bell <on|off>         Ring the terminal bell when an alarm fires
+      --on-alarm <CMD>        Shell command run when an alarm fires
+      --on-recover <CMD>      Shell command run when an alarm recovers
+      --hook-interval <SECS>  Least time between runs of a hook (default: 30)
+      --hook-timeout <SECS>   Kill a hook still running after SECS (default: 10)
+  -h, --help                  Print help
+  -V, --version               Print version";
+
+/// Options that only apply to this run and never come from the config file.
+const RUN_OPTIONS: [&str; 6] = ["config", "record", "replay", "width", "height", "duration"];
+
+/// Run options that take no value.
+const RUN_FLAGS: [&str; 1] = ["snapshot"];
+
+/// Options that only apply with `--snapshot`.
+const SNAPSHOT_OPTIONS: [&str; 3] = ["width", "height", "duration"];
+
+const SNAPSHOT_SECS: f64 = 5.0;
+
+/// Options that select processes for the process source and imply it.
+const PROCESS_FILTERS: [&str; 3] = ["pid", "name", "user"];
+
+pub enum Command {
+    Run(Invocation),
+    Help,
+    Version,
+}
+
+/// Options given on the command line. They are kept as raw flag/value pairs so
+/// they can be laid over the config file again every time it is reloaded.
+pub struct Invocation {
+    pub config: Option<PathBuf>,
+    /// File every sample is written to, in a format chosen by its extension.
+    pub record: Option<PathBuf>,
+    /// Recorded session to play back instead of sampling live.
+    pub replay: Option<PathBuf>,
+    /// Print one frame instead of running interactively.
+    pub snapshot: Option<Snapshot>,
+    overrides: Vec<(String, String)>,
+}
+
+/// Size and sampling time of a `--snapshot` frame. A missing size is taken
+/// from the terminal.
+pub struct Snapshot {
+    pub width: Option<u16>,
+    pub height: Option<u16>,
+    pub duration: Duration,
+}
+
+impl Invocation {
+    pub fn apply(&self, settings: &mut Settings) -> Result<(), String> {
+        for (flag, value) in &self.overrides {
+            set(settings, flag, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses the arguments after the program name. Each value is checked on its
+/// own here; relations between values are checked once the config file has
+/// been merged in.
+pub fn parse<I>(args: I) -> Result<Command, String>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut invocation = Invocation {
+        config: None,
+        record: None,
+        replay: None,
+        snapshot: None,
+        overrides: Vec::new(),
+    };
+    let mut snapshot = false;
+    let mut width = None;
+    let mut height = None;
+    let mut duration = None;
+    let mut snapshot_option = None;
+    let mut scratch = Settings::default();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "-h" || arg == "--help" {
+            return Ok(Command::Help);
+        }
+        if arg == "-V" || arg == "--version" {
+            return Ok(Command::Version);
+        }
+        let Some(flag) = arg.strip_prefix("--") else {
+            return Err(format!("unexpected argument '{arg}'"));
+        };
+        let (flag, inline) = match flag.split_once('=') {
+            Some((flag, value)) => (flag, Some(value.to_string())),
+            None => (flag, None),
+        };
+        if RUN_FLAGS.contains(&flag) {
+            if inline.is_some() {
+                return Err(format!("--{flag} does not take a value"));
+            }
+            snapshot = true;
+            continue;
+        }
+        if !RUN_OPTIONS.contains(&flag) && !Settings::OPTIONS.contains(&flag) {
+            return Err(format!("unknown option '--{flag}'"));
+        }
+        let value = match inline {
+            Some(value) => value,
+            None => args
+                .next()
+                .ok_or_else(|| format!("--{flag} needs a value"))?,
+        };
+        if SNAPSHOT_OPTIONS.contains(&flag) {
+            snapshot_option.get_or_insert_with(|| flag.to_string());
+        }
+        match flag {
+            "config" => {
+                invocation.config = Some(PathBuf::from(value));
+                continue;
+            }
+            "record" => {
+                invocation.record = Some(PathBuf::from(value));
+                continue;
+            }
+            "replay" => {
+                invocation.replay = Some(PathBuf::from(value));
+                continue;
+            }
+            "width" => {
+                width = Some(parse_size(flag, &value)?);
+                continue;
+            }
+            "height" => {
+                height = Some(parse_size(flag, &value)?);
+                continue;
+            }
+            "duration" => {
+                duration = Some(parse_secs(flag, &value)?);
+                continue;
+            }
+            _ => {}
+        }
+        set(&mut scratch, flag, &value)?;
+        invocation.overrides.push((flag.to_string(), value));
+    }
+    if snapshot {
+        invocation.snapshot = Some(Snapshot {
+            width,
+            height,
+            duration: duration.unwrap_or(Duration::from_secs_f64(SNAPSHOT_SECS)),
+        });
+    } else if let Some(flag) = snapshot_option {
+        return Err(format!("--{flag} only applies with --snapshot"));
+    }
+    // Process filters only make sense with the process source, which they
+    // imply unless another source was asked for.
+    let value = |name: &str| {
+        invocation
+            .overrides
+            .iter()
+            .rev()
+            .find(|(flag, _)| flag == name)
+            .map(|(_, value)| value.as_str())
+    };
+    if let Some(filter) = PROCESS_FILTERS.iter().find(|&&name| value(name).is_some()) {
+        match value("source") {
+            None => invocation
+                .overrides
+                .push(("source".to_string(), "process".to_string())),
+            Some("process") => {}
+            Some(source) => {
+                return Err(format!(
+                    "--{filter} only applies with --source process, not {source}"
+                ));
+            }
+        }
+    }
+    Ok(Command::Run(invocation))
+}
+
+fn set(settings: &mut Settings, flag: &str, value: &str) -> Result<(), String> {
+    settings.set(flag, value).map_err(|err| match err {
+        SetError::Unknown => format!("unknown option '--{flag}'"),
+        SetError::Invalid(reason) => format!("invalid value '{value}' for --{flag}: {reason}"),
+    })
+}
+
+fn parse_size(flag: &str, value: &str) -> Result<u16, String> {
+    value
+        .parse()
+        .ok()
+        .filter(|&size: &u16| size > 0)
+        .ok_or_else(|| format!("invalid value '{value}' for --{flag}: expected a positive number"))
+}
+
+fn parse_secs(flag: &str, value: &str) -> Result<Duration, String> {
+    value
+        .parse()
+        .ok()
+        .and_then(|secs: f64| Duration::try_from_secs_f64(secs).ok())
+        .filter(|duration| !duration.is_zero())
+        .ok_or_else(|| {
+            format!("invalid value '{value}' for --{flag}: expected a positive number of seconds")
+        })
+}
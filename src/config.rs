@@ -0,0 +1,182 @@
+//! `config.toml` loading and hot reload.
+//!
+//! Top-level keys mirror the command-line options with underscores instead of
+//! dashes (`fps_min = 15`). Colors and key bindings live in `[colors]` and
+//! `[keys]` tables. Command-line flags always win over the file.
+
+use crate::cli::Invocation;
+use crate::settings::{parse_color, SetError, Settings};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use toml::{Table, Value};
+
+const RELOAD_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+const APP_DIR: &str = "ecg-cpu";
+const FILE_NAME: &str = "config.toml";
+
+/// `$XDG_CONFIG_HOME/ecg-cpu/config.toml`, falling back to `~/.config` on Unix
+/// and `%APPDATA%` on Windows.
+pub fn default_path() -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+    };
+    base.map(|dir| dir.join(APP_DIR).join(FILE_NAME))
+}
+
+/// Builds settings from the defaults, then the config file when `path` is
+/// given, then the command-line flags.
+pub fn resolve(path: Option<&Path>, invocation: &Invocation) -> Result<Settings, String> {
+    let mut settings = Settings::default();
+    if let Some(path) = path {
+        let text = fs::read_to_string(path)
+            .map_err(|err| format!("cannot read {}: {err}", path.display()))?;
+        apply(&mut settings, &text).map_err(|err| format!("{}: {err}", path.display()))?;
+    }
+    invocation.apply(&mut settings)?;
+    settings.validate()?;
+    Ok(settings)
+}
+
+fn apply(settings: &mut Settings, text: &str) -> Result<(), String> {
+    let table: Table = text
+        .parse()
+        .map_err(|err: toml::de::Error| match err.span() {
+            Some(span) => {
+                let line = text[..span.start].matches('\n').count() + 1;
+                format!("line {line}: {}", err.message())
+            }
+            None => err.message().to_string(),
+        })?;
+    for (key, value) in &table {
+        match key.as_str() {
+            "colors" => apply_colors(settings, expect_table(key, value)?)?,
+            "keys" => apply_keys(settings, expect_table(key, value)?)?,
+            _ => {
+                let name = key.replace('_', "-");
+                let text = scalar(key, value)?;
+                settings.set(&name, &text).map_err(|err| match err {
+                    SetError::Unknown => format!("unknown key '{key}'"),
+                    SetError::Invalid(reason) => format!("invalid value for '{key}': {reason}"),
+                })?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn apply_colors(settings: &mut Settings, table: &Table) -> Result<(), String> {
+    for (key, value) in table {
+        let slot = match key.as_str() {
+            "ok" => &mut settings.palette.ok,
+            "warn" => &mut settings.palette.warn,
+            "crit" => &mut settings.palette.crit,
+            "grid" => &mut settings.palette.grid,
+            "text" => &mut settings.palette.text,
+            "overlay" => &mut settings.palette.overlay,
+            _ => return Err(format!("unknown key 'colors.{key}'")),
+        };
+        let name = expect_str(key, value)?;
+        *slot = parse_color(name)
+            .ok_or_else(|| format!("invalid value for 'colors.{key}': unknown color '{name}'"))?;
+    }
+    Ok(())
+}
+
+fn apply_keys(settings: &mut Settings, table: &Table) -> Result<(), String> {
+    for (key, value) in table {
+        let slot = match key.as_str() {
+            "quit" => &mut settings.keys.quit,
+            "fps_up" => &mut settings.keys.fps_up,
+            "fps_down" => &mut settings.keys.fps_down,
+            "source" => &mut settings.keys.source,
+            "model" => &mut settings.keys.model,
+            "renderer" => &mut settings.keys.renderer,
+            "display" => &mut settings.keys.display,
+            "plot" => &mut settings.keys.plot,
+            "pause" => &mut settings.keys.pause,
+            "faster" => &mut settings.keys.faster,
+            "slower" => &mut settings.keys.slower,
+            "acknowledge" => &mut settings.keys.acknowledge,
+            "freeze" => &mut settings.keys.freeze,
+            _ => return Err(format!("unknown key 'keys.{key}'")),
+        };
+        *slot = expect_str(key, value)?.to_string();
+    }
+    Ok(())
+}
+
+fn expect_table<'a>(key: &str, value: &'a Value) -> Result<&'a Table, String> {
+    value
+        .as_table()
+        .ok_or_else(|| format!("'{key}' must be a table"))
+}
+
+fn expect_str<'a>(key: &str, value: &'a Value) -> Result<&'a str, String> {
+    value
+        .as_str()
+        .ok_or_else(|| format!("'{key}' must be a string"))
+}
+
+/// The option text for a value. Arrays become comma-separated lists, so
+/// `alarm = ["> 90% for 10s", "< 5% for 1m"]` works like the flag.
+fn scalar(key: &str, value: &Value) -> Result<String, String> {
+    match value {
+        Value::String(text) => Ok(text.clone()),
+        Value::Integer(number) => Ok(number.to_string()),
+        Value::Float(number) => Ok(number.to_string()),
+        Value::Boolean(switch) => Ok(switch.to_string()),
+        Value::Array(items) => Ok(items
+            .iter()
+            .map(|item| scalar(key, item))
+            .collect::<Result<Vec<_>, _>>()?
+            .join(",")),
+        _ => Err(format!("'{key}' must be a string or a number")),
+    }
+}
+
+/// Polls the config file's modification time so edits are picked up while the
+/// monitor runs.
+pub struct Watcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    next_check: Instant,
+}
+
+impl Watcher {
+    pub fn new(path: PathBuf) -> Self {
+        let modified = modified(&path);
+        Self {
+            path,
+            modified,
+            next_check: Instant::now() + RELOAD_CHECK_INTERVAL,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// True once per change of the file, including it appearing or vanishing.
+    pub fn changed(&mut self, now: Instant) -> bool {
+        if now < self.next_check {
+            return false;
+        }
+        self.next_check = now + RELOAD_CHECK_INTERVAL;
+        let modified = modified(&self.path);
+        if modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+        true
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
@@ -0,0 +1,93 @@
+//! Where samples come from: a live source sampled in the background, or a
+//! recorded session played back.
+
+use crate::replay::Replay;
+use crate::sampler::Sampler;
+use crate::source::{MetricSource, Sample};
+use std::time::{Duration, Instant};
+
+pub enum Feed {
+    Live(Box<Sampler>),
+    Replay(Replay),
+}
+
+impl Feed {
+    /// Opens a source with `open` in the background and samples it every
+    /// `interval`.
+    pub fn live<F>(open: F, interval: Duration) -> Self
+    where
+        F: FnOnce() -> Box<dyn MetricSource> + Send + 'static,
+    {
+        Feed::Live(Box::new(Sampler::spawn(open, interval)))
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Feed::Live(sampler) => sampler.name(),
+            Feed::Replay(replay) => replay.name(),
+        }
+    }
+
+    /// Replayed readings have lost their unit and are shown as plain numbers.
+    pub fn unit(&self) -> &str {
+        match self {
+            Feed::Live(sampler) => sampler.unit(),
+            Feed::Replay(_) => "",
+        }
+    }
+
+    /// Whether a zero reading counts as asystole; a replay cannot tell.
+    pub fn zero_is_failure(&self) -> bool {
+        match self {
+            Feed::Live(sampler) => sampler.zero_is_failure(),
+            Feed::Replay(_) => false,
+        }
+    }
+
+    /// True once after a live source has been opened, when its name, unit and
+    /// [`Feed::zero_is_failure`] become known.
+    pub fn take_opened(&mut self) -> bool {
+        match self {
+            Feed::Live(sampler) => sampler.take_opened(),
+            Feed::Replay(_) => false,
+        }
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        match self {
+            Feed::Live(sampler) => sampler.error(),
+            Feed::Replay(_) => None,
+        }
+    }
+
+    pub fn set_interval(&self, interval: Duration) {
+        if let Feed::Live(sampler) = self {
+            sampler.set_interval(interval);
+        }
+    }
+
+    /// The sample for `now`, or `None` before the first live reading.
+    pub fn sample_at(&mut self, now: Instant) -> Option<Sample> {
+        match self {
+            Feed::Live(sampler) => {
+                sampler.poll();
+                sampler.sample_at(now)
+            }
+            Feed::Replay(replay) => Some(replay.sample_at(now)),
+        }
+    }
+
+    pub fn take_events(&mut self) -> u32 {
+        match self {
+            Feed::Live(sampler) => sampler.take_events(),
+            Feed::Replay(_) => 0,
+        }
+    }
+
+    pub fn replay(&mut self) -> Option<&mut Replay> {
+        match self {
+            Feed::Live(_) => None,
+            Feed::Replay(replay) => Some(replay),
+        }
+    }
+}
@@ -0,0 +1,87 @@
+//! Trace history for freezing the display and scrolling back.
+//!
+//! Every lead keeps its recent samples in a bounded buffer next to the
+//! on-screen one. While the display is frozen the plot shows a window of that
+//! buffer instead, and sampling carries on underneath.
+
+use std::collections::VecDeque;
+
+/// How much history each lead keeps.
+pub const HISTORY_SECS: usize = 3600;
+
+/// Synthesized sample and sampled level, oldest first, dropping the oldest
+/// once `capacity` is reached.
+pub struct History {
+    entries: VecDeque<(f32, f32)>,
+    capacity: usize,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, sample: f32, level: f32) {
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((sample, level));
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// The `width` entries ending `behind` entries before the newest, with
+    /// `None` in front when the history does not reach back that far.
+    pub fn window(&self, behind: usize, width: usize) -> Vec<Option<(f32, f32)>> {
+        let end = self.entries.len().saturating_sub(behind);
+        let start = end.saturating_sub(width);
+        let mut window = vec![None; width - (end - start)];
+        window.extend(self.entries.range(start..end).copied().map(Some));
+        window
+    }
+}
+
+/// What the plot shows: the live trace, or a frozen one `behind` samples
+/// before the newest.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum View {
+    Live,
+    Frozen { behind: usize },
+}
+
+impl View {
+    /// Freezes on the newest sample, or goes back to live.
+    pub fn toggled(self) -> Self {
+        match self {
+            View::Live => View::Frozen { behind: 0 },
+            View::Frozen { .. } => View::Live,
+        }
+    }
+
+    /// Moves `samples` further back, or forward when negative, freezing a
+    /// live view. `limit` is how far back the history reaches.
+    pub fn scroll(self, samples: isize, limit: usize) -> Self {
+        let behind = match self {
+            View::Live => 0,
+            View::Frozen { behind } => behind,
+        };
+        View::Frozen {
+            behind: behind.saturating_add_signed(samples).min(limit),
+        }
+    }
+
+    /// Keeps a frozen view on the same samples while `added` new ones arrive.
+    pub fn follow(self, added: usize, limit: usize) -> Self {
+        match self {
+            View::Live => View::Live,
+            View::Frozen { behind } => View::Frozen {
+                behind: (behind + added).min(limit),
+            },
+        }
+    }
+}
@@ -0,0 +1,203 @@
+//! User commands run when an alarm fires or recovers.
+//!
+//! Each hook runs through the shell on its own thread, so the render loop
+//! never waits for it. A hook that is still running, or that ran less than the
+//! rate-limit interval ago, is skipped, and one that outlives its timeout is
+//! killed along with everything it started. Details of the alarm are passed
+//! in `ECG_CPU_*` environment variables.
+
+use crate::alarm::{AlarmEvent, Change, Comparison};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// What the hook describes, besides the alarm itself.
+pub struct Reading<'a> {
+    pub metric: &'a str,
+    pub unit: &'a str,
+    pub raw: f64,
+}
+
+/// Commands and limits for the hooks, taken from the settings on every run so
+/// a reloaded config applies to the next alarm.
+pub struct HookConfig<'a> {
+    pub on_alarm: Option<&'a str>,
+    pub on_recover: Option<&'a str>,
+    pub interval: Duration,
+    pub timeout: Duration,
+}
+
+#[derive(Default)]
+struct Slot {
+    last_run: Option<Instant>,
+    running: Arc<AtomicBool>,
+}
+
+#[derive(Default)]
+pub struct Hooks {
+    alarm: Slot,
+    recover: Slot,
+    error: Arc<Mutex<Option<String>>>,
+}
+
+impl Hooks {
+    /// Starts the hook for `change` unless it is rate limited or still busy.
+    pub fn run(&mut self, config: &HookConfig<'_>, change: &Change, reading: &Reading<'_>) {
+        let (name, command, slot) = match change.event {
+            AlarmEvent::Fired => ("on-alarm", config.on_alarm, &mut self.alarm),
+            AlarmEvent::Recovered => ("on-recover", config.on_recover, &mut self.recover),
+        };
+        let Some(command) = command else {
+            return;
+        };
+        let now = Instant::now();
+        let limited = slot
+            .last_run
+            .is_some_and(|last| now.duration_since(last) < config.interval);
+        if limited || slot.running.load(Ordering::Relaxed) {
+            return;
+        }
+        slot.last_run = Some(now);
+        slot.running.store(true, Ordering::Relaxed);
+
+        let mut process = shell(command);
+        process
+            .envs(environment(change, reading))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        let running = Arc::clone(&slot.running);
+        let error = Arc::clone(&self.error);
+        let timeout = config.timeout;
+        thread::spawn(move || {
+            let result = supervise(&mut process, timeout);
+            *error.lock().unwrap_or_else(|e| e.into_inner()) =
+                result.err().map(|err| format!("{name} hook {err}"));
+            running.store(false, Ordering::Relaxed);
+        });
+    }
+
+    /// Why the last hook to finish failed, if it did.
+    pub fn error(&self) -> Option<String> {
+        self.error.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+/// The shell runs in a process group of its own, so a timeout can kill the
+/// commands it started too.
+#[cfg(unix)]
+fn shell(command: &str) -> Command {
+    use std::os::unix::process::CommandExt;
+
+    let mut process = Command::new("sh");
+    process.arg("-c").arg(command).process_group(0);
+    process
+}
+
+#[cfg(not(unix))]
+fn shell(command: &str) -> Command {
+    let mut process = Command::new("cmd");
+    process.arg("/C").arg(command);
+    process
+}
+
+fn environment(change: &Change, reading: &Reading<'_>) -> Vec<(&'static str, String)> {
+    let event = match change.event {
+        AlarmEvent::Fired => "alarm",
+        AlarmEvent::Recovered => "recover",
+    };
+    let comparison = match change.rule.comparison {
+        Comparison::Above => "above",
+        Comparison::Below => "below",
+    };
+    vec![
+        ("ECG_CPU_EVENT", event.to_string()),
+        ("ECG_CPU_RULE", change.rule.to_string()),
+        ("ECG_CPU_METRIC", reading.metric.to_string()),
+        ("ECG_CPU_VALUE", format!("{:.1}", change.load * 100.0)),
+        ("ECG_CPU_RAW", format!("{:.3}", reading.raw)),
+        ("ECG_CPU_UNIT", reading.unit.to_string()),
+        ("ECG_CPU_COMPARISON", comparison.to_string()),
+        (
+            "ECG_CPU_THRESHOLD",
+            format!("{:.1}", change.rule.threshold * 100.0),
+        ),
+        (
+            "ECG_CPU_DURATION",
+            format!("{:.1}", change.duration.as_secs_f64()),
+        ),
+    ]
+}
+
+#[cfg(unix)]
+fn kill(child: &mut Child) {
+    // SAFETY: plain syscall; the negative pid names the group the shell leads.
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+}
+
+#[cfg(not(unix))]
+fn kill(child: &mut Child) {
+    let _ = child.kill();
+}
+
+/// Runs `process` to completion, killing it once `timeout` has passed.
+fn supervise(process: &mut Command, timeout: Duration) -> Result<(), String> {
+    let mut child = process
+        .spawn()
+        .map_err(|err| format!("failed to start: {err}"))?;
+    let deadline = Instant::now() + timeout;
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) if status.success() => return Ok(()),
+            Ok(Some(status)) => return Err(format!("failed: {status}")),
+            Ok(None) if Instant::now() >= deadline => {
+                kill(&mut child);
+                let _ = child.wait();
+                return Err(format!("killed after {}s timeout", timeout.as_secs_f64()));
+            }
+            Ok(None) => thread::sleep(POLL_INTERVAL),
+            Err(err) => return Err(format!("failed: {err}")),
+        }
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// True while `pid` is a live process, not gone or a zombie.
+    fn alive(pid: &str) -> bool {
+        fs::read_to_string(format!("/proc/{pid}/stat"))
+            .is_ok_and(|stat| !stat.rsplit(')').next().unwrap_or("").starts_with(" Z"))
+    }
+
+    #[test]
+    fn timeout_kills_the_whole_command() {
+        let pid_file =
+            std::env::temp_dir().join(format!("ecg-cpu-hook-{}.pid", std::process::id()));
+        let command = format!("sleep 30 & echo $! > {}; wait", pid_file.display());
+        let result = supervise(&mut shell(&command), Duration::from_millis(300));
+        assert!(result.unwrap_err().starts_with("killed after"));
+
+        let pid = fs::read_to_string(&pid_file).unwrap();
+        let _ = fs::remove_file(&pid_file);
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while alive(pid.trim()) {
+            assert!(Instant::now() < deadline, "sleep {} survived", pid.trim());
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    #[test]
+    fn reports_failing_status() {
+        let result = supervise(&mut shell("exit 3"), Duration::from_secs(5));
+        assert!(result.unwrap_err().starts_with("failed: exit status: 3"));
+    }
+}
@@ -1,34 +1,84 @@
-use crossterm::cursor::{Hide, MoveTo, Show};
+mod alarm;
+mod beat;
+mod canvas;
+mod cli;
+mod config;
+mod feed;
+mod history;
+mod hook;
+mod panel;
+mod record;
+mod replay;
+mod rhythm;
+mod sampler;
+mod settings;
+mod source;
+mod term;
+
+use alarm::{AlarmEvent, Alarms};
+use beat::{Beat, BeatTemplate};
+use canvas::BrailleCanvas;
+use cli::{Command, Invocation};
+use config::Watcher;
+use crossterm::cursor::MoveTo;
 use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
-use crossterm::style::{Color, Print, ResetColor, SetForegroundColor};
-use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
-use crossterm::{execute, QueueableCommand};
-use std::io::{self, Write};
-use std::time::{Duration, Instant};
-use sysinfo::System;
+use crossterm::style::{Attribute, Color, Print, ResetColor, SetAttribute, SetForegroundColor};
+use crossterm::terminal::{self, Clear, ClearType};
+use crossterm::QueueableCommand;
+use feed::Feed;
+use history::{History, View};
+use hook::{Hooks, Reading};
+use panel::{LoadSummary, LoadWindow};
+use record::{Mark, Record, Recorder};
+use replay::Replay;
+use rhythm::{Detector, Rhythm};
+use settings::{Action, Display, Keymap, Plot, Renderer, Settings, Source, WaveModel};
+use source::Sample;
+use std::io::{self, IsTerminal, Write};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use term::TerminalGuard;
 
 const HEADER_ROWS: u16 = 1;
 const FOOTER_ROWS: u16 = 1;
 const LEFT_GUTTER: u16 = 5;
-const GRID_ROW_STEP: usize = 4;
-const GRID_COL_STEP: usize = 6;
 const MIN_PLOT_HEIGHT: usize = 4;
 const MIN_PLOT_WIDTH: usize = 10;
-const MILLIS_PER_SEC: u64 = 1000;
+const LEAD_LABEL_WIDTH: usize = 4;
+const LEAD_LEVEL_GLYPHS: [char; 5] = ['_', '.', '-', '~', '^'];
+/// Right-hand panel: a separator, then three big digits with two cells of
+/// margin on each side.
+const PANEL_WIDTH: u16 = panel::big_width(3) as u16 + 5;
+/// Narrowest terminal that still gets the panel.
+const PANEL_MIN_TERMINAL_WIDTH: u16 = 80;
+/// Snapshot size when neither the command line nor a terminal gives one.
+const SNAPSHOT_WIDTH: u16 = 80;
+const SNAPSHOT_HEIGHT: u16 = 24;
+
+const FPS_STEP: u32 = 5;
+/// How far the arrow keys scroll a frozen display.
+const SCROLL_STEP_SECS: f32 = 1.0;
+const SECS_PER_MIN: usize = 60;
+/// Half period of the header flash while an alarm is pending.
+const FLASH_MILLIS: u128 = 500;
 
-const FPS_DEFAULT: u32 = 30;
-const FPS_MIN: u32 = 10;
-const FPS_MAX: u32 = 60;
+/// Samples added to each trace per second of wall-clock time, independent of
+/// the frame rate.
+const SAMPLE_RATE_HZ: f32 = 30.0;
+const SAMPLE_PERIOD: f32 = 1.0 / SAMPLE_RATE_HZ;
+/// Samples each lead keeps for scrolling back.
+const HISTORY_SAMPLES: usize = history::HISTORY_SECS * SAMPLE_RATE_HZ as usize;
 
-const PHASE_DELTA_BASE: f32 = 0.25;
-const PHASE_DELTA_LOAD_SCALE: f32 = 0.7;
+/// Sine phase speed in radians per second.
+const PHASE_RATE_BASE: f32 = 7.5;
+const PHASE_RATE_LOAD_SCALE: f32 = 21.0;
 const PHASE_WRAP: f32 = 1000.0;
 const PERCENT_SCALE: f32 = 100.0;
 
-const PULSE_LOAD_THRESHOLD: f32 = 0.7;
-const PULSE_INTERVAL_TICKS: u64 = 18;
+const PULSE_INTERVAL_SECS: f32 = 0.6;
 const PULSE_PEAK: f32 = 1.0;
-const PULSE_DECAY: f32 = 0.65;
+/// Exponential pulse decay per second.
+const PULSE_DECAY_RATE: f32 = 12.9;
 const PULSE_GAIN: f32 = 0.9;
 
 const BASE_AMPLITUDE: f32 = 0.7;
@@ -39,150 +89,869 @@ const LOW_LOAD_PHASE_SCALE: f32 = 0.7;
 const SIGNAL_MIN: f32 = -1.0;
 const SIGNAL_MAX: f32 = 1.0;
 const SIGNAL_RANGE: f32 = SIGNAL_MAX - SIGNAL_MIN;
-const START_TICK: u64 = 1;
 const TAU: f32 = std::f32::consts::TAU;
 
-const FOOTER_TEXT: &str = "Press Q/Esc to quit  +/- to change FPS";
+fn clamp_sample(value: f32) -> f32 {
+    value.clamp(SIGNAL_MIN, SIGNAL_MAX)
+}
 
-fn read_cpu_usage(sys: &mut System) -> f32 {
-    sys.refresh_cpu_all();
-    let usage = sys.global_cpu_usage() / 100.0;
-    usage.clamp(0.0, 1.0)
+/// Waveform settings shared by every lead.
+struct Synth {
+    model: WaveModel,
+    template: BeatTemplate,
+    pulse_threshold: f32,
+    /// Rhythm the detectors currently see in the load.
+    rhythm: Rhythm,
 }
 
-fn line_color(load: f32) -> Color {
-    if load < 0.5 {
-        Color::Green
-    } else if load < 0.75 {
-        Color::Yellow
-    } else {
-        Color::Red
+struct Oscillator {
+    phase: f32,
+    pulse: f32,
+    /// Seconds since the last pulse slot; pulses can only fire on slots.
+    pulse_clock: f32,
+    beat: Beat,
+    /// Set when a pulse fires, until [`Oscillator::take_mark`].
+    fired: bool,
+}
+
+impl Oscillator {
+    fn new() -> Self {
+        Self {
+            phase: 0.0,
+            pulse: 0.0,
+            pulse_clock: 0.0,
+            beat: Beat::new(),
+            fired: false,
+        }
+    }
+
+    /// Produces the next sample, `dt` seconds after the previous one.
+    fn step(&mut self, synth: &Synth, load: f32, dt: f32) -> f32 {
+        if synth.rhythm == Rhythm::Fibrillation {
+            self.phase = (self.phase + dt) % PHASE_WRAP;
+            return clamp_sample(beat::fibrillation(self.phase));
+        }
+        match synth.model {
+            WaveModel::Pqrst => {
+                let template = match synth.rhythm {
+                    Rhythm::Tachycardia => synth.template.tachycardia(),
+                    _ => synth.template,
+                };
+                let sample = self.beat.step(&template, load, dt) + self.pulse * PULSE_GAIN;
+                self.pulse *= (-PULSE_DECAY_RATE * dt).exp();
+                clamp_sample(sample)
+            }
+            WaveModel::Sine => self.step_sine(synth.pulse_threshold, load, dt),
+        }
+    }
+
+    /// Fires a pulse on top of the waveform for an event reported by the
+    /// source, such as a throttled period.
+    fn kick(&mut self) {
+        self.pulse = PULSE_PEAK;
+        self.fired = true;
+    }
+
+    /// Fires an early beat, or a pulse in the sine model.
+    fn premature(&mut self, synth: &Synth) {
+        match synth.model {
+            WaveModel::Pqrst => self.beat.premature(&synth.template),
+            WaveModel::Sine => self.kick(),
+        }
+    }
+
+    /// The pulse or R peak produced since the last call, if any.
+    fn take_mark(&mut self) -> Option<Mark> {
+        let peaked = self.beat.take_peak();
+        if std::mem::take(&mut self.fired) {
+            Some(Mark::Pulse)
+        } else if peaked {
+            Some(Mark::Beat)
+        } else {
+            None
+        }
+    }
+
+    fn step_sine(&mut self, pulse_threshold: f32, load: f32, dt: f32) -> f32 {
+        self.pulse_clock += dt;
+        if self.pulse_clock >= PULSE_INTERVAL_SECS {
+            self.pulse_clock -= PULSE_INTERVAL_SECS;
+            if load > pulse_threshold {
+                self.pulse = PULSE_PEAK;
+                self.fired = true;
+            }
+        }
+        self.pulse *= (-PULSE_DECAY_RATE * dt).exp();
+
+        let base = BASE_AMPLITUDE * self.phase.sin();
+        let mut sample = base + self.pulse * PULSE_GAIN;
+        if load < LOW_LOAD_THRESHOLD {
+            sample = LOW_LOAD_AMPLITUDE * (self.phase * LOW_LOAD_PHASE_SCALE).sin();
+        }
+        self.phase += phase_rate(load) * dt;
+        if self.phase > PHASE_WRAP {
+            self.phase = 0.0;
+        }
+        clamp_sample(sample)
     }
 }
 
-fn clamp_sample(value: f32) -> f32 {
-    value.clamp(SIGNAL_MIN, SIGNAL_MAX)
+fn phase_rate(load: f32) -> f32 {
+    PHASE_RATE_BASE + load * PHASE_RATE_LOAD_SCALE
 }
 
-struct RenderMetrics {
+/// Speed of the drawn sine in radians per second; below the low-load
+/// threshold the wave runs on a scaled phase.
+fn oscillation_rate(load: f32) -> f32 {
+    if load < LOW_LOAD_THRESHOLD {
+        phase_rate(load) * LOW_LOAD_PHASE_SCALE
+    } else {
+        phase_rate(load)
+    }
+}
+
+/// One ECG trace: a labelled signal with its own oscillator and sample history.
+///
+/// In scroll mode `samples` is oldest-first. In sweep mode it is indexed by
+/// screen position and `head` is the slot the next sample overwrites.
+/// `levels` holds the sampled level behind each sample, laid out the same way.
+/// `history` keeps both, oldest first, for as far back as a frozen display can
+/// scroll.
+struct Lead {
+    label: String,
     load: f32,
-    phase: f32,
-    pulse: f32,
-    fps: u32,
-    phase_delta: f32,
+    osc: Oscillator,
+    samples: Vec<f32>,
+    levels: Vec<f32>,
+    head: usize,
+    history: History,
 }
 
-fn render(
-    stdout: &mut io::Stdout,
-    samples: &[f32],
-    metrics: RenderMetrics,
-    full_clear: bool,
-) -> io::Result<()> {
-    let (width, height) = terminal::size()?;
-    if width == 0 || height == 0 {
-        return Ok(());
+impl Lead {
+    fn new(label: String) -> Self {
+        Self {
+            label,
+            load: 0.0,
+            osc: Oscillator::new(),
+            samples: Vec::new(),
+            levels: Vec::new(),
+            head: 0,
+            history: History::new(HISTORY_SAMPLES),
+        }
     }
 
-    let plot_height = height.saturating_sub(HEADER_ROWS + FOOTER_ROWS) as usize;
-    let plot_width = width.saturating_sub(LEFT_GUTTER) as usize;
-    if plot_height < MIN_PLOT_HEIGHT || plot_width < MIN_PLOT_WIDTH {
-        return Ok(());
+    /// Adds the next sample and returns it; a `None` level draws the flat
+    /// baseline.
+    fn advance(
+        &mut self,
+        synth: &Synth,
+        level: Option<f32>,
+        width: usize,
+        display: Display,
+    ) -> f32 {
+        self.load = level.unwrap_or(0.0);
+        let sample = match level {
+            Some(load) => self.osc.step(synth, load, SAMPLE_PERIOD),
+            None => 0.0,
+        };
+        self.history.push(sample, self.load);
+        if self.samples.is_empty() {
+            self.samples.resize(width, sample);
+            self.levels.resize(width, self.load);
+            return sample;
+        }
+        self.head %= width;
+        for (buffer, value) in [(&mut self.samples, sample), (&mut self.levels, self.load)] {
+            let fill = buffer.last().copied().unwrap_or(value);
+            resize_samples(buffer, width, fill);
+            match display {
+                Display::Scroll => {
+                    buffer.push(value);
+                    if buffer.len() > width {
+                        buffer.remove(0);
+                    }
+                }
+                Display::Sweep => buffer[self.head] = value,
+            }
+        }
+        if display == Display::Sweep {
+            self.head = (self.head + 1) % width;
+        }
+        sample
+    }
+
+    /// Synthesizes a sample that only goes into the history, for samples that
+    /// would scroll off the screen before being drawn, and returns it.
+    fn skip(&mut self, synth: &Synth, level: Option<f32>) -> f32 {
+        self.load = level.unwrap_or(0.0);
+        let sample = match level {
+            Some(load) => self.osc.step(synth, load, SAMPLE_PERIOD),
+            None => 0.0,
+        };
+        self.history.push(sample, self.load);
+        sample
     }
 
-    let mut buffer = vec![vec![' '; plot_width]; plot_height];
-    for row in (0..plot_height).step_by(GRID_ROW_STEP) {
-        for col in (0..plot_width).step_by(GRID_COL_STEP) {
-            buffer[row][col] = '.';
+    /// Switching to scroll puts the sweep buffer back in time order; switching
+    /// to sweep starts the write head over the oldest sample.
+    fn set_display(&mut self, display: Display) {
+        if display == Display::Scroll && self.head < self.samples.len() {
+            self.samples.rotate_left(self.head);
+            self.levels.rotate_left(self.head);
         }
+        self.head = 0;
     }
-    let mut trace_points: Vec<(usize, usize, char)> = Vec::new();
-    let mut prev_y: Option<usize> = None;
 
-    for (x, &sample) in samples.iter().enumerate().take(plot_width) {
+    /// Samples in screen order, with `None` for the sweep erase gap ahead of
+    /// the write head. Raw plots map the level from `0.0..=1.0` onto the
+    /// signal range so both go through the same renderers.
+    fn visible(&self, display: Display, gap: usize, raw: bool) -> Vec<Option<f32>> {
+        let mut visible: Vec<Option<f32>> = if raw {
+            self.levels
+                .iter()
+                .map(|&level| Some(SIGNAL_MIN + level * SIGNAL_RANGE))
+                .collect()
+        } else {
+            self.samples.iter().copied().map(Some).collect()
+        };
+        if display == Display::Sweep && !visible.is_empty() {
+            let len = visible.len();
+            for offset in 0..gap.min(len) {
+                visible[(self.head + offset) % len] = None;
+            }
+        }
+        visible
+    }
+
+    /// The `width` samples ending `behind` samples before the newest, in time
+    /// order, for a frozen display.
+    fn frozen(&self, behind: usize, width: usize, raw: bool) -> Vec<Option<f32>> {
+        self.history
+            .window(behind, width)
+            .into_iter()
+            .map(|entry| {
+                entry.map(|(sample, level)| {
+                    if raw {
+                        SIGNAL_MIN + level * SIGNAL_RANGE
+                    } else {
+                        sample
+                    }
+                })
+            })
+            .collect()
+    }
+}
+
+/// The main lead plus one lead per channel for multi-channel sources, and an
+/// optional secondary lead drawn over the main one.
+struct Traces {
+    main: Lead,
+    channels: Vec<Lead>,
+    overlay: Option<Lead>,
+    detector: Detector,
+    load_window: LoadWindow,
+}
+
+impl Traces {
+    fn new(label: &str, zero_is_failure: bool) -> Self {
+        Self {
+            main: Lead::new(label.to_string()),
+            channels: Vec::new(),
+            overlay: None,
+            detector: Detector::new(zero_is_failure),
+            load_window: LoadWindow::new(),
+        }
+    }
+
+    /// Runs the arrhythmia detectors on the latest sample and fires a
+    /// premature beat on every lead when a spike starts.
+    fn assess(&mut self, synth: &Synth, sample: &Sample, failed: bool, now: Instant) -> Rhythm {
+        let assessment = self.detector.update(now, sample.value, failed);
+        if assessment.premature {
+            for lead in self.channels.iter_mut().chain([&mut self.main]) {
+                lead.osc.premature(synth);
+            }
+        }
+        assessment.rhythm
+    }
+
+    fn set_display(&mut self, display: Display) {
+        self.main.set_display(display);
+        for lead in self.channels.iter_mut().chain(&mut self.overlay) {
+            lead.set_display(display);
+        }
+    }
+
+    /// Advances every lead by one sample and returns the main lead's value.
+    fn advance(&mut self, synth: &Synth, sample: &Sample, width: usize, display: Display) -> f32 {
+        self.step(sample, |lead, level| {
+            lead.advance(synth, level, width, display)
+        })
+    }
+
+    /// Advances every lead by one sample that is kept only in the history and
+    /// returns the main lead's value.
+    fn skip(&mut self, synth: &Synth, sample: &Sample) -> f32 {
+        self.step(sample, |lead, level| lead.skip(synth, level))
+    }
+
+    /// Runs `step` on every lead with its level, `None` for a flat line, and
+    /// returns what it gave for the main lead.
+    fn step(
+        &mut self,
+        sample: &Sample,
+        mut step: impl FnMut(&mut Lead, Option<f32>) -> f32,
+    ) -> f32 {
+        if self.channels.len() != sample.channels.len() {
+            self.channels = sample
+                .channels
+                .iter()
+                .map(|channel| Lead::new(channel.label.clone()))
+                .collect();
+        }
+        let live = |level: f32| (!sample.flatline).then_some(level);
+        let value = step(&mut self.main, live(sample.value));
+        for (lead, channel) in self.channels.iter_mut().zip(&sample.channels) {
+            step(lead, live(channel.value));
+        }
+        match sample.overlay {
+            Some(level) => {
+                let lead = self.overlay.get_or_insert_with(|| Lead::new(String::new()));
+                step(lead, live(level));
+            }
+            None => self.overlay = None,
+        }
+        value
+    }
+
+    fn leads(&self) -> &[Lead] {
+        if self.channels.is_empty() {
+            std::slice::from_ref(&self.main)
+        } else {
+            &self.channels
+        }
+    }
+
+    /// The overlay lead, which only shares the plot with a single main lead.
+    fn overlay(&self) -> Option<&Lead> {
+        self.overlay.as_ref().filter(|_| self.channels.is_empty())
+    }
+}
+
+/// Row band assigned to one lead inside the plot area.
+struct LeadSlot {
+    lead: usize,
+    top: usize,
+    rows: usize,
+}
+
+/// Stacks leads top to bottom, splitting spare rows over the first leads. When
+/// there are more leads than rows, each visible lead gets a single row and the
+/// busiest leads win, so a pegged core is never hidden behind idle ones.
+fn layout_leads(leads: &[Lead], plot_height: usize) -> Vec<LeadSlot> {
+    if leads.is_empty() || plot_height == 0 {
+        return Vec::new();
+    }
+    let mut visible: Vec<usize> = (0..leads.len()).collect();
+    if leads.len() > plot_height {
+        visible.sort_by(|&a, &b| leads[b].load.total_cmp(&leads[a].load));
+        visible.truncate(plot_height);
+        visible.sort_unstable();
+    }
+
+    let rows = plot_height / visible.len();
+    let extra = plot_height % visible.len();
+    let mut top = 0;
+    visible
+        .into_iter()
+        .enumerate()
+        .map(|(slot, lead)| {
+            let rows = rows + usize::from(slot < extra);
+            let band = LeadSlot { lead, top, rows };
+            top += rows;
+            band
+        })
+        .collect()
+}
+
+fn trace_points(
+    samples: &[Option<f32>],
+    plot_width: usize,
+    rows: usize,
+) -> Vec<(usize, usize, char)> {
+    let mut points = Vec::new();
+    if rows == 1 {
+        let top = (LEAD_LEVEL_GLYPHS.len() - 1) as f32;
+        for (x, sample) in samples.iter().enumerate().take(plot_width) {
+            let Some(sample) = *sample else {
+                continue;
+            };
+            let normalized = (sample - SIGNAL_MIN) / SIGNAL_RANGE;
+            let level = (normalized * top).round() as usize;
+            let glyph = LEAD_LEVEL_GLYPHS[level.min(LEAD_LEVEL_GLYPHS.len() - 1)];
+            points.push((x, 0, glyph));
+        }
+        return points;
+    }
+
+    let mut prev_y: Option<usize> = None;
+    for (x, sample) in samples.iter().enumerate().take(plot_width) {
+        let Some(sample) = *sample else {
+            prev_y = None;
+            continue;
+        };
         let normalized = (sample - SIGNAL_MIN) / SIGNAL_RANGE;
-        let y = ((1.0 - normalized) * (plot_height as f32 - 1.0)).round() as usize;
-        let y = y.min(plot_height - 1);
-        trace_points.push((x, y, '*'));
+        let y = ((1.0 - normalized) * (rows as f32 - 1.0)).round() as usize;
+        let y = y.min(rows - 1);
+        points.push((x, y, '*'));
 
         match prev_y {
             Some(prev) if prev != y => {
                 let (min_y, max_y) = if prev < y { (prev, y) } else { (y, prev) };
                 for row in (min_y + 1)..max_y {
-                    trace_points.push((x, row, '|'));
+                    points.push((x, row, '|'));
                 }
             }
             _ => {}
         }
         prev_y = Some(y);
     }
+    points
+}
+
+/// Samples drawn per terminal column: braille packs two dot columns per cell.
+fn samples_per_cell(renderer: Renderer) -> usize {
+    match renderer {
+        Renderer::Braille => BrailleCanvas::dots_per_cell_x(),
+        Renderer::Ascii => 1,
+    }
+}
+
+fn braille_points(
+    samples: &[Option<f32>],
+    plot_width: usize,
+    rows: usize,
+) -> Vec<(usize, usize, char)> {
+    let mut canvas = BrailleCanvas::new(plot_width, rows);
+    let bottom = canvas.dot_height() - 1;
+    let mut prev_y: Option<usize> = None;
+    for (x, sample) in samples.iter().enumerate().take(canvas.dot_width()) {
+        let Some(sample) = *sample else {
+            prev_y = None;
+            continue;
+        };
+        let normalized = (sample - SIGNAL_MIN) / SIGNAL_RANGE;
+        let y = ((1.0 - normalized) * bottom as f32).round() as usize;
+        let y = y.min(bottom);
+        canvas.vline(x, prev_y.unwrap_or(y), y);
+        prev_y = Some(y);
+    }
+    canvas.cells().collect()
+}
+
+struct RenderMetrics<'a> {
+    name: &'a str,
+    unit: &'a str,
+    raw: f64,
+    detail: Option<&'a str>,
+    flatline: bool,
+    load: f32,
+    model: WaveModel,
+    renderer: Renderer,
+    display: Display,
+    plot: Plot,
+    replay: bool,
+    view: View,
+    phase: f32,
+    pulse: f32,
+    bpm: f32,
+    beat: f32,
+    /// Rate the load maps to, for the panel.
+    heart_rate: f32,
+    load_summary: Option<LoadSummary>,
+    fps: u32,
+    phase_rate: f32,
+    rhythm: Rhythm,
+    /// Message shown in place of the key help.
+    status: Option<&'a str>,
+    /// Header drawn in inverse video, for the on half of an alarm flash.
+    flash: bool,
+}
+
+/// Where a frame is drawn.
+#[derive(Clone, Copy)]
+enum Target {
+    /// The alternate screen, redrawn in place.
+    Screen { full_clear: bool },
+    /// Lines of text, with ANSI colors when `color` is set.
+    Text { color: bool },
+}
+
+/// One character cell of a frame.
+#[derive(Clone, Copy)]
+struct Cell {
+    ch: char,
+    color: Color,
+    /// Drawn in inverse video.
+    reverse: bool,
+}
+
+impl Cell {
+    fn new(ch: char, color: Color) -> Self {
+        Self {
+            ch,
+            color,
+            reverse: false,
+        }
+    }
 
-    let osc_hz = if metrics.phase_delta > 0.0 {
-        (metrics.phase_delta * metrics.fps as f32) / TAU
+    fn same_style(&self, other: &Cell) -> bool {
+        self.color == other.color && self.reverse == other.reverse
+    }
+}
+
+/// Columns taken by the right-hand panel, or zero when the screen is too
+/// small for it.
+fn panel_width(width: u16, height: u16) -> u16 {
+    let plot_height = height.saturating_sub(HEADER_ROWS + FOOTER_ROWS) as usize;
+    if width >= PANEL_MIN_TERMINAL_WIDTH && plot_height > panel::DIGIT_ROWS {
+        PANEL_WIDTH
+    } else {
+        0
+    }
+}
+
+/// Columns left for the trace between the gutter and the panel.
+fn plot_width(width: u16, height: u16) -> usize {
+    width.saturating_sub(LEFT_GUTTER + panel_width(width, height)) as usize
+}
+
+/// True when a `width` x `height` screen leaves room for the plot.
+fn fits(width: u16, height: u16) -> bool {
+    let plot_height = height.saturating_sub(HEADER_ROWS + FOOTER_ROWS) as usize;
+    plot_height >= MIN_PLOT_HEIGHT && plot_width(width, height) >= MIN_PLOT_WIDTH
+}
+
+fn render<W: Write>(
+    out: &mut W,
+    (width, height): (u16, u16),
+    leads: &[Lead],
+    overlay: Option<&Lead>,
+    metrics: RenderMetrics<'_>,
+    settings: &Settings,
+    target: Target,
+) -> io::Result<()> {
+    if !fits(width, height) {
+        return Ok(());
+    }
+
+    let plot_height = height.saturating_sub(HEADER_ROWS + FOOTER_ROWS) as usize;
+    let plot_width = plot_width(width, height);
+    let blank = Cell::new(' ', settings.palette.grid);
+    let mut frame = vec![vec![blank; width as usize]; height as usize];
+    if let Some(grid) = settings.grid {
+        for row in (0..plot_height).step_by(grid.row_step) {
+            for col in (0..plot_width).step_by(grid.col_step) {
+                frame[HEADER_ROWS as usize + row][LEFT_GUTTER as usize + col].ch = '.';
+            }
+        }
+    }
+    let slots = layout_leads(leads, plot_height);
+
+    let reading = if metrics.unit == "%" {
+        format!("{:>5.1}%", metrics.raw)
     } else {
-        0.0
+        format!(
+            "{:>6.2}{}  level: {:>3.0}%",
+            metrics.raw,
+            metrics.unit,
+            metrics.load * PERCENT_SCALE
+        )
+    };
+    let mut header = format!("{} ECG  {}", metrics.name, reading);
+    if let View::Frozen { behind } = metrics.view {
+        let secs = (behind as f32 / SAMPLE_RATE_HZ).round() as usize;
+        header.push_str(&format!(
+            "  PAUSED / -{:02}:{:02}",
+            secs / SECS_PER_MIN,
+            secs % SECS_PER_MIN
+        ));
+    }
+    if let Some(label) = metrics.rhythm.label() {
+        header.push_str(&format!("  {label}"));
+    }
+    if let Some(detail) = metrics.detail {
+        header.push_str(&format!("  {detail}"));
+    }
+    header.push_str(&format!("  fps: {:>2}", metrics.fps));
+    // Neither a flat line nor fibrillation has a countable rate.
+    let rate_known = !metrics.flatline && metrics.rhythm != Rhythm::Fibrillation;
+    match metrics.model {
+        _ if !rate_known => {
+            header.push_str("  bpm: ---");
+        }
+        WaveModel::Pqrst => {
+            header.push_str(&format!(
+                "  bpm: {:>3.0}  beat: {:>4.2}",
+                metrics.bpm, metrics.beat
+            ));
+        }
+        WaveModel::Sine => {
+            let osc_hz = metrics.phase_rate / TAU;
+            header.push_str(&format!(
+                "  osc: {:>4.2}Hz  phase: {:>5.1}  pulse: {:>4.2}",
+                osc_hz, metrics.phase, metrics.pulse
+            ));
+        }
+    }
+    if leads.len() > 1 {
+        header.push_str(&format!("  leads: {}/{}", slots.len(), leads.len()));
+    }
+    let (footer, footer_color) = match metrics.status {
+        Some(message) => (message.to_string(), settings.palette.crit),
+        // Key help means nothing in a printed frame.
+        None if matches!(target, Target::Text { .. }) => (String::new(), settings.palette.grid),
+        None => (
+            footer_text(&settings.keys, metrics.replay, metrics.view),
+            settings.palette.grid,
+        ),
     };
-    let header = format!(
-        "CPU ECG  load: {:>5.1}%  fps: {:>2}  osc: {:>4.2}Hz  phase: {:>5.1}  pulse: {:>4.2}",
-        metrics.load * PERCENT_SCALE,
-        metrics.fps,
-        osc_hz,
-        metrics.phase,
-        metrics.pulse
-    );
-    let footer = FOOTER_TEXT;
 
-    if full_clear {
-        stdout.queue(Clear(ClearType::All))?;
+    put_text(&mut frame[0], &header, settings.palette.text);
+    if metrics.flash {
+        for cell in &mut frame[0] {
+            cell.color = settings.palette.crit;
+            cell.reverse = true;
+        }
     }
-    stdout.queue(MoveTo(0, 0))?;
-    stdout.queue(SetForegroundColor(Color::White))?;
-    stdout.queue(Print(pad_to_width(&header, width)))?;
 
-    stdout.queue(SetForegroundColor(Color::DarkGrey))?;
+    let axis_labels = match metrics.plot {
+        Plot::Raw => ["100%|", " 50%|", "  0%|"],
+        Plot::Synthetic | Plot::Combined => [" 1.0|", " 0.0|", "-1.0|"],
+    };
     let axis_top = 0usize;
     let axis_mid = plot_height / 2;
     let axis_bottom = plot_height.saturating_sub(1);
-    for (row, line) in buffer.into_iter().enumerate() {
-        let y = HEADER_ROWS + row as u16;
-        let gutter = if row == axis_top {
-            " 1.0|"
+    for row in 0..plot_height {
+        let gutter = if leads.len() > 1 {
+            match slots.iter().find(|slot| slot.top == row) {
+                Some(slot) => {
+                    let label = &leads[slot.lead].label;
+                    let label: String = label.chars().take(LEAD_LABEL_WIDTH).collect();
+                    format!("{label:>LEAD_LABEL_WIDTH$}|")
+                }
+                None => format!("{:>LEAD_LABEL_WIDTH$}|", ""),
+            }
+        } else if row == axis_top {
+            axis_labels[0].to_string()
         } else if row == axis_mid {
-            " 0.0|"
+            axis_labels[1].to_string()
         } else if row == axis_bottom {
-            "-1.0|"
+            axis_labels[2].to_string()
         } else {
-            "     "
+            continue;
         };
-        stdout.queue(MoveTo(0, y))?;
-        stdout.queue(Print(gutter))?;
-        stdout.queue(MoveTo(LEFT_GUTTER, y))?;
-        let line_string: String = line.into_iter().collect();
-        stdout.queue(Print(line_string))?;
+        put_text(
+            &mut frame[HEADER_ROWS as usize + row],
+            &gutter,
+            settings.palette.grid,
+        );
     }
 
-    stdout.queue(SetForegroundColor(line_color(metrics.load)))?;
-    for (x, y, ch) in trace_points {
-        let draw_y = HEADER_ROWS + y as u16;
-        stdout.queue(MoveTo(LEFT_GUTTER + x as u16, draw_y))?;
-        stdout.queue(Print(ch))?;
+    let erase_gap = settings.erase_gap * samples_per_cell(metrics.renderer);
+    let capacity = plot_width * samples_per_cell(metrics.renderer);
+    let raw = metrics.plot == Plot::Raw;
+    // Combined plots put the sampled level behind each lead in a dim color.
+    let backdrop = slots
+        .iter()
+        .filter(|_| metrics.plot == Plot::Combined)
+        .map(|slot| (&leads[slot.lead], slot, settings.palette.grid, true));
+    let overlay = overlay
+        .zip(slots.first())
+        .map(|(lead, slot)| (lead, slot, settings.palette.overlay, raw));
+    let traces = backdrop.chain(overlay).chain(slots.iter().map(|slot| {
+        let lead = &leads[slot.lead];
+        (lead, slot, settings.line_color(lead.load), raw)
+    }));
+    for (lead, slot, color, raw) in traces {
+        let samples = match metrics.view {
+            View::Live => lead.visible(metrics.display, erase_gap, raw),
+            View::Frozen { behind } => lead.frozen(behind, capacity, raw),
+        };
+        let points = match metrics.renderer {
+            Renderer::Braille => braille_points(&samples, plot_width, slot.rows),
+            Renderer::Ascii => trace_points(&samples, plot_width, slot.rows),
+        };
+        for (x, y, ch) in points {
+            let row = HEADER_ROWS as usize + slot.top + y;
+            if let Some(cell) = frame[row].get_mut(LEFT_GUTTER as usize + x) {
+                *cell = Cell::new(ch, color);
+            }
+        }
     }
 
-    stdout.queue(SetForegroundColor(Color::DarkGrey))?;
-    stdout.queue(MoveTo(0, height.saturating_sub(1)))?;
-    stdout.queue(Print(pad_to_width(footer, width)))?;
-    stdout.queue(ResetColor)?;
-    stdout.flush()?;
-    Ok(())
+    let panel = panel_width(width, height) as usize;
+    if panel > 0 {
+        let left = width as usize - panel;
+        let rows = &mut frame[HEADER_ROWS as usize..][..plot_height];
+        for row in rows.iter_mut() {
+            row[left].ch = '|';
+        }
+        let heart_rate = if rate_known {
+            format!("{:>3.0}", metrics.heart_rate)
+        } else {
+            "---".to_string()
+        };
+        let rate_color = if rate_known {
+            settings.line_color(metrics.load)
+        } else {
+            settings.palette.crit
+        };
+        let pixel = match metrics.renderer {
+            Renderer::Braille => '\u{2588}',
+            Renderer::Ascii => '#',
+        };
+        let digits = panel::big_text(&heart_rate, pixel);
+        let mut lines: Vec<(String, Color)> = vec![("HR bpm".to_string(), settings.palette.text)];
+        lines.extend(digits.into_iter().map(|line| (line, rate_color)));
+        if let Some(summary) = &metrics.load_summary {
+            lines.push((String::new(), settings.palette.text));
+            lines.push(("load, last minute".to_string(), settings.palette.text));
+            for (label, level) in [
+                ("min", summary.min),
+                ("max", summary.max),
+                ("avg", summary.average),
+            ] {
+                lines.push((
+                    format!("{label} {:>5.1}%", level * PERCENT_SCALE),
+                    settings.palette.text,
+                ));
+            }
+        }
+        for (row, (text, color)) in rows.iter_mut().zip(&lines) {
+            put_text(&mut row[left + 2..], text, *color);
+        }
+    }
+
+    if let Some(last) = frame.last_mut() {
+        put_text(last, &footer, footer_color);
+    }
+    match target {
+        Target::Screen { full_clear } => draw_screen(out, &frame, full_clear),
+        Target::Text { color } => print_text(out, &frame, color),
+    }
+}
+
+/// Writes `text` over the start of `row`, cut to fit.
+fn put_text(row: &mut [Cell], text: &str, color: Color) {
+    for (cell, ch) in row.iter_mut().zip(text.chars()) {
+        *cell = Cell::new(ch, color);
+    }
+}
+
+/// Draws `frame` on the terminal, one color change per run of cells.
+fn draw_screen<W: Write>(out: &mut W, frame: &[Vec<Cell>], full_clear: bool) -> io::Result<()> {
+    if full_clear {
+        out.queue(Clear(ClearType::All))?;
+    }
+    for (y, row) in frame.iter().enumerate() {
+        out.queue(MoveTo(0, y as u16))?;
+        for run in row.chunk_by(Cell::same_style) {
+            out.queue(SetForegroundColor(run[0].color))?;
+            out.queue(SetAttribute(reverse_attribute(run[0].reverse)))?;
+            out.queue(Print(run.iter().map(|cell| cell.ch).collect::<String>()))?;
+        }
+    }
+    out.queue(SetAttribute(Attribute::NoReverse))?;
+    out.queue(ResetColor)?;
+    out.flush()
 }
 
-fn pad_to_width(text: &str, width: u16) -> String {
-    let max = width as usize;
-    let mut out: String = text.chars().take(max).collect();
-    let len = out.chars().count();
-    if len < max {
-        out.extend(std::iter::repeat_n(' ', max - len));
+/// Prints `frame` as lines without trailing blanks. Colors are written as ANSI
+/// escapes whatever the platform, since the output may be captured.
+fn print_text<W: Write>(out: &mut W, frame: &[Vec<Cell>], color: bool) -> io::Result<()> {
+    for row in frame {
+        let end = row
+            .iter()
+            .rposition(|cell| cell.ch != ' ')
+            .map_or(0, |last| last + 1);
+        for run in row[..end].chunk_by(|a, b| a.same_style(b) || !color) {
+            if color {
+                write!(
+                    out,
+                    "{}{}",
+                    SetForegroundColor(run[0].color),
+                    SetAttribute(reverse_attribute(run[0].reverse))
+                )?;
+            }
+            let text: String = run.iter().map(|cell| cell.ch).collect();
+            out.write_all(text.as_bytes())?;
+        }
+        if color && end > 0 {
+            write!(out, "{}{ResetColor}", SetAttribute(Attribute::NoReverse))?;
+        }
+        writeln!(out)?;
     }
-    out
+    out.flush()
+}
+
+fn reverse_attribute(reverse: bool) -> Attribute {
+    if reverse {
+        Attribute::Reverse
+    } else {
+        Attribute::NoReverse
+    }
+}
+
+/// Key help. Replays swap the source key for the playback controls, and a
+/// frozen display lists the scroll keys first.
+fn footer_text(keys: &Keymap, replay: bool, view: View) -> String {
+    let freeze = match view {
+        View::Live => format!("{} freeze", keys.label(Action::Freeze)),
+        View::Frozen { .. } => {
+            return format!(
+                "{}/End live  Left/Right PgUp/PgDn scroll  Home oldest  {}/Esc quit",
+                keys.label(Action::Freeze),
+                keys.label(Action::Quit)
+            );
+        }
+    };
+    let feed = if replay {
+        format!(
+            "{} pause  {}/{} speed  Left/Right seek",
+            keys.label(Action::Pause),
+            keys.label(Action::Slower),
+            keys.label(Action::Faster)
+        )
+    } else {
+        format!("{} source", keys.label(Action::Source))
+    };
+    format!(
+        "{}/Esc quit  {}/{} FPS  {feed}  {freeze}  {} waveform  {} renderer  {} sweep  {} plot",
+        keys.label(Action::Quit),
+        keys.label(Action::FpsUp),
+        keys.label(Action::FpsDown),
+        keys.label(Action::Model),
+        keys.label(Action::Renderer),
+        keys.label(Action::Display),
+        keys.label(Action::Plot)
+    )
+}
+
+/// Reads the config file when it exists, or always when it was named on the
+/// command line so a typo in `--config` is reported.
+fn load_settings(watcher: Option<&Watcher>, invocation: &Invocation) -> Result<Settings, String> {
+    let path = watcher
+        .map(Watcher::path)
+        .filter(|path| invocation.config.is_some() || path.exists());
+    config::resolve(path, invocation)
+}
+
+/// Samples `source` in the background, opening it off the render thread since
+/// some sources take a first reading when they are created.
+fn live_feed(source: Source, settings: &Settings) -> Feed {
+    let options = settings.clone();
+    Feed::live(move || source::open(source, &options), settings.interval())
 }
 
 fn resize_samples(samples: &mut Vec<f32>, width: usize, fill: f32) {
@@ -199,97 +968,444 @@ fn resize_samples(samples: &mut Vec<f32>, width: usize, fill: f32) {
 }
 
 fn main() -> io::Result<()> {
+    let invocation = match cli::parse(std::env::args().skip(1)) {
+        Ok(Command::Run(invocation)) => invocation,
+        Ok(Command::Help) => {
+            println!("{}", cli::USAGE);
+            return Ok(());
+        }
+        Ok(Command::Version) => {
+            println!("ecg-cpu {}", env!("CARGO_PKG_VERSION"));
+            return Ok(());
+        }
+        Err(message) => {
+            eprintln!("ecg-cpu: {message}");
+            eprintln!("Try 'ecg-cpu --help' for more information.");
+            std::process::exit(2);
+        }
+    };
+    let mut watcher = invocation
+        .config
+        .clone()
+        .or_else(config::default_path)
+        .map(Watcher::new);
+    let mut settings = match load_settings(watcher.as_ref(), &invocation) {
+        Ok(settings) => settings,
+        Err(message) => {
+            eprintln!("ecg-cpu: {message}");
+            std::process::exit(2);
+        }
+    };
+
+    let recorder = match invocation.record.as_deref().map(Recorder::create) {
+        Some(Ok(recorder)) => Some(recorder),
+        Some(Err(message)) => {
+            eprintln!("ecg-cpu: {message}");
+            std::process::exit(2);
+        }
+        None => None,
+    };
+
+    let replay = match invocation.replay.as_deref() {
+        Some(path) => match Replay::open(path, settings.interval()) {
+            Ok(replay) => Some(replay),
+            Err(message) => {
+                eprintln!("ecg-cpu: {message}");
+                std::process::exit(2);
+            }
+        },
+        None => None,
+    };
+
     let mut stdout = io::stdout();
-    terminal::enable_raw_mode()?;
-    execute!(stdout, EnterAlternateScreen, Hide)?;
-
-    let mut fps: u32 = FPS_DEFAULT;
-    let mut phase: f32 = 0.0;
-    let mut pulse: f32 = 0.0;
-    let mut sys = System::new();
-    let mut samples: Vec<f32> = Vec::new();
+    // A snapshot draws at a fixed size and never takes over the terminal.
+    let fixed_size = invocation.snapshot.as_ref().map(|snapshot| {
+        let terminal = stdout
+            .is_terminal()
+            .then(|| terminal::size().ok())
+            .flatten()
+            .unwrap_or((SNAPSHOT_WIDTH, SNAPSHOT_HEIGHT));
+        (
+            snapshot.width.unwrap_or(terminal.0),
+            snapshot.height.unwrap_or(terminal.1),
+        )
+    });
+    if let Some((width, height)) = fixed_size
+        && !fits(width, height)
+    {
+        eprintln!("ecg-cpu: a {width}x{height} snapshot leaves no room for the plot");
+        std::process::exit(2);
+    }
+    let deadline = invocation
+        .snapshot
+        .as_ref()
+        .map(|snapshot| Instant::now() + snapshot.duration);
+    let guard = match fixed_size {
+        Some(_) => None,
+        None => Some(TerminalGuard::enter()?),
+    };
+    // Lets the terminal tests check the teardown after a panic.
+    #[cfg(feature = "test-panic")]
+    if guard.is_some() && std::env::var_os("ECG_CPU_TEST_PANIC").is_some() {
+        panic!("ECG_CPU_TEST_PANIC is set");
+    }
+
+    let mut fps: u32 = settings.fps;
+    let mut selected_source = settings.source;
+    let mut renderer = settings.renderer;
+    let mut display = settings.display;
+    let mut plot = settings.plot;
+    let mut synth = Synth {
+        model: settings.model,
+        template: settings.beat_template(),
+        pulse_threshold: settings.pulse_threshold,
+        rhythm: Rhythm::Sinus,
+    };
+    let mut feed = match replay {
+        Some(replay) => Feed::Replay(replay),
+        None => live_feed(selected_source, &settings),
+    };
+    let mut traces = Traces::new(feed.name(), feed.zero_is_failure());
     let mut last_draw = Instant::now();
-    let mut tick: u64 = START_TICK;
-    let mut last_size = terminal::size().unwrap_or((0, 0));
-    let mut seeded = false;
+    let mut last_sample = last_draw;
+    let mut sample_debt: f32 = 0.0;
+    let mut last_size = fixed_size.unwrap_or_else(|| terminal::size().unwrap_or((0, 0)));
+    let mut force_clear = false;
+    let mut status: Option<String> = None;
+    let mut alarms = Alarms::new(&settings.alarms, settings.alarm_hysteresis);
+    let mut hooks = Hooks::default();
+    let mut view = View::Live;
+    let started = Instant::now();
 
     loop {
+        if guard
+            .as_ref()
+            .is_some_and(TerminalGuard::shutdown_requested)
+        {
+            break;
+        }
         let now = Instant::now();
         let elapsed = now.duration_since(last_draw);
-        let tick_rate = Duration::from_millis(MILLIS_PER_SEC / u64::from(fps.max(1)));
-        if elapsed < tick_rate {
-            if event::poll(tick_rate - elapsed)?
-                && let Event::Key(KeyEvent { code, modifiers, .. }) = event::read()?
+        let tick_rate = Duration::from_secs_f64(1.0 / f64::from(fps.max(1)));
+        let wait = tick_rate.saturating_sub(elapsed);
+        // Keys are read on every pass, so even a frame rate with no time
+        // left between frames still answers them.
+        if guard.is_some() && event::poll(wait)? {
+            if let Event::Key(KeyEvent {
+                code, modifiers, ..
+            }) = event::read()?
             {
-                if code == KeyCode::Char('q') || code == KeyCode::Esc {
+                if code == KeyCode::Esc {
                     break;
                 }
                 if code == KeyCode::Char('c') && modifiers.contains(KeyModifiers::CONTROL) {
                     break;
                 }
-                if code == KeyCode::Char('+') || code == KeyCode::Char('=') {
-                    fps = (fps + 5).min(FPS_MAX);
+                if let KeyCode::Char(key) = code {
+                    match settings.keys.action(key) {
+                        Some(Action::Quit) => break,
+                        Some(Action::FpsUp) => fps = (fps + FPS_STEP).min(settings.fps_max),
+                        Some(Action::FpsDown) => {
+                            fps = fps.saturating_sub(FPS_STEP).max(settings.fps_min);
+                        }
+                        Some(Action::Source) if matches!(feed, Feed::Live(_)) => {
+                            selected_source = selected_source.next();
+                            feed = live_feed(selected_source, &settings);
+                            traces = Traces::new(feed.name(), feed.zero_is_failure());
+                            force_clear = true;
+                        }
+                        Some(Action::Source) => {}
+                        Some(Action::Model) => synth.model = synth.model.toggled(),
+                        Some(Action::Renderer) => {
+                            renderer = renderer.toggled();
+                            force_clear = true;
+                        }
+                        Some(Action::Display) => {
+                            display = display.toggled();
+                            traces.set_display(display);
+                            force_clear = true;
+                        }
+                        Some(Action::Plot) => {
+                            plot = plot.next();
+                            force_clear = true;
+                        }
+                        Some(Action::Pause) => {
+                            if let Some(replay) = feed.replay() {
+                                replay.toggle_pause();
+                            }
+                        }
+                        Some(Action::Faster) => {
+                            if let Some(replay) = feed.replay() {
+                                replay.faster();
+                            }
+                        }
+                        Some(Action::Slower) => {
+                            if let Some(replay) = feed.replay() {
+                                replay.slower();
+                            }
+                        }
+                        Some(Action::Acknowledge) => alarms.acknowledge(),
+                        Some(Action::Freeze) => view = view.toggled(),
+                        None => {}
+                    }
                 }
-                if code == KeyCode::Char('-') || code == KeyCode::Char('_') {
-                    fps = fps.saturating_sub(5).max(FPS_MIN);
+                // Scrolling stays within what is not already on screen.
+                let page = plot_width(last_size.0, last_size.1) * samples_per_cell(renderer);
+                let limit = traces.main.history.len().saturating_sub(page);
+                let step = (SCROLL_STEP_SECS * SAMPLE_RATE_HZ) as isize;
+                // Arrows seek a replay unless the display is frozen.
+                match (code, feed.replay()) {
+                    (KeyCode::Left, Some(replay)) if view == View::Live => {
+                        replay.seek(-replay::SEEK_STEP_SECS);
+                    }
+                    (KeyCode::Right, Some(replay)) if view == View::Live => {
+                        replay.seek(replay::SEEK_STEP_SECS);
+                    }
+                    (KeyCode::Left, _) => view = view.scroll(step, limit),
+                    (KeyCode::Right, _) => view = view.scroll(-step, limit),
+                    (KeyCode::PageUp, _) => view = view.scroll(page as isize, limit),
+                    (KeyCode::PageDown, _) => view = view.scroll(-(page as isize), limit),
+                    (KeyCode::Home, _) => view = View::Frozen { behind: limit },
+                    (KeyCode::End, _) => view = View::Live,
+                    _ => {}
                 }
             }
             continue;
         }
+        if !wait.is_zero() {
+            if guard.is_none() {
+                thread::sleep(wait);
+            }
+            continue;
+        }
         last_draw = now;
 
-        let load = read_cpu_usage(&mut sys);
-
-        let (width, height) = terminal::size()?;
-        let plot_width = width.saturating_sub(LEFT_GUTTER) as usize;
-        if height > HEADER_ROWS + FOOTER_ROWS && plot_width > 0 {
-            let phase_delta = PHASE_DELTA_BASE + load * PHASE_DELTA_LOAD_SCALE;
-            if load > PULSE_LOAD_THRESHOLD && tick.is_multiple_of(PULSE_INTERVAL_TICKS) {
-                pulse = PULSE_PEAK;
+        if let Some(watcher) = watcher.as_mut()
+            && watcher.changed(now)
+        {
+            match load_settings(Some(watcher), &invocation) {
+                Ok(next) => {
+                    if next.fps != settings.fps {
+                        fps = next.fps;
+                    }
+                    fps = fps.clamp(next.fps_min, next.fps_max);
+                    let source_changed =
+                        next.source != settings.source || !next.same_source_options(&settings);
+                    if source_changed && matches!(feed, Feed::Live(_)) {
+                        if next.source != settings.source {
+                            selected_source = next.source;
+                        }
+                        feed = live_feed(selected_source, &next);
+                        traces = Traces::new(feed.name(), feed.zero_is_failure());
+                    }
+                    if next.model != settings.model {
+                        synth.model = next.model;
+                    }
+                    if next.renderer != settings.renderer {
+                        renderer = next.renderer;
+                    }
+                    if next.display != settings.display {
+                        display = next.display;
+                        traces.set_display(display);
+                    }
+                    if next.plot != settings.plot {
+                        plot = next.plot;
+                    }
+                    if next.alarms != settings.alarms
+                        || next.alarm_hysteresis != settings.alarm_hysteresis
+                    {
+                        alarms = Alarms::new(&next.alarms, next.alarm_hysteresis);
+                    }
+                    synth.template = next.beat_template();
+                    synth.pulse_threshold = next.pulse_threshold;
+                    feed.set_interval(next.interval());
+                    settings = next;
+                    status = None;
+                }
+                Err(message) => status = Some(format!("config error: {message}")),
             }
-            pulse *= PULSE_DECAY;
+            force_clear = true;
+        }
 
-            let base = BASE_AMPLITUDE * phase.sin();
-            let mut sample = base + pulse * PULSE_GAIN;
-            if load < LOW_LOAD_THRESHOLD {
-                sample = LOW_LOAD_AMPLITUDE * (phase * LOW_LOAD_PHASE_SCALE).sin();
+        let reading = feed.sample_at(now);
+        if feed.take_opened() {
+            // Only now is it known what the main lead is called and whether a
+            // zero reading means the source stopped.
+            traces = Traces::new(feed.name(), feed.zero_is_failure());
+        }
+        // The idle placeholder is only drawn; nothing that keeps statistics
+        // or writes files may take it for a real 0%. A source that fails
+        // before its first reading still shows as asystole.
+        let measured = reading.is_some();
+        let mut sample = reading.unwrap_or_else(Sample::idle);
+        if !measured {
+            synth.rhythm = rhythm::unmeasured(feed.error().is_some());
+        } else {
+            synth.rhythm = traces.assess(
+                &synth,
+                &sample,
+                sample.flatline || feed.error().is_some(),
+                now,
+            );
+            if synth.rhythm == Rhythm::Asystole {
+                sample.flatline = true;
             }
-            let sample = clamp_sample(sample);
-            phase += phase_delta;
-            if phase > PHASE_WRAP {
-                phase = 0.0;
+            traces.load_window.push(now, sample.value);
+        }
+        if feed.take_events() > 0 {
+            traces.main.osc.kick();
+        }
+        let changes = if measured {
+            alarms.update(sample.value, now)
+        } else {
+            Vec::new()
+        };
+        for change in changes {
+            if change.event == AlarmEvent::Fired && settings.bell && guard.is_some() {
+                stdout.queue(Print('\x07'))?;
             }
-            let full_clear = (width, height) != last_size;
+            let reading = Reading {
+                metric: feed.name(),
+                unit: feed.unit(),
+                raw: sample.raw,
+            };
+            hooks.run(&settings.hooks(), &change, &reading);
+        }
+
+        let (width, height) = match fixed_size {
+            Some(size) => size,
+            None => terminal::size()?,
+        };
+        let plot_width = plot_width(width, height);
+        sample_debt += now.duration_since(last_sample).as_secs_f32() * SAMPLE_RATE_HZ;
+        last_sample = now;
+        let mut due = sample_debt.floor() as usize;
+        sample_debt -= due as f32;
+
+        let drawable = height > HEADER_ROWS + FOOTER_ROWS && plot_width > 0;
+        let capacity = if drawable {
+            plot_width * samples_per_cell(renderer)
+        } else {
+            0
+        };
+        if drawable && traces.main.samples.is_empty() {
+            due = due.max(1);
+        }
+        // After a stall, anything older than a screenful would scroll straight
+        // off again, and with no room to draw nothing fits at all, so those
+        // samples only go into the history. Every one is still recorded, since
+        // recordings tell time by counting samples.
+        let skipped = due.saturating_sub(capacity);
+        let wall_clock = SystemTime::now();
+        for step in 0..due {
+            let value = if step < skipped {
+                traces.skip(&synth, &sample)
+            } else {
+                traces.advance(&synth, &sample, capacity, display)
+            };
+            if let Some(recorder) = &recorder
+                && measured
+            {
+                // Samples caught up in one frame are spread back over the time
+                // they stand for.
+                let age = SAMPLE_PERIOD * (due - 1 - step) as f32;
+                let osc = &mut traces.main.osc;
+                recorder.push(Record {
+                    time: wall_clock - Duration::from_secs_f32(age),
+                    source: feed.name().to_string(),
+                    raw: sample.raw,
+                    level: sample.value,
+                    value,
+                    phase: osc.phase,
+                    pulse: osc.pulse,
+                    bpm: osc.beat.bpm(),
+                    beat: osc.beat.progress(),
+                    mark: osc.take_mark(),
+                });
+            }
+        }
+        let limit = traces.main.history.len().saturating_sub(capacity);
+        view = view.follow(due, limit);
+
+        if drawable {
+            let full_clear = (width, height) != last_size || force_clear;
             if full_clear {
                 last_size = (width, height);
-            }
-            if !seeded {
-                samples.clear();
-                samples.resize(plot_width, sample);
-                seeded = true;
-            } else {
-                let fill = samples.last().copied().unwrap_or(sample);
-                resize_samples(&mut samples, plot_width, fill);
-                samples.push(sample);
-                if samples.len() > plot_width {
-                    samples.remove(0);
-                }
+                force_clear = false;
             }
 
+            let record_error = recorder.as_ref().and_then(Recorder::error);
+            let hook_error = hooks.error();
+            let banner = alarms.pending().map(|pending| {
+                format!(
+                    "ALARM{}: {}  ({} to acknowledge)",
+                    if pending.recovered {
+                        " (recovered)"
+                    } else {
+                        ""
+                    },
+                    pending.rule,
+                    settings.keys.label(Action::Acknowledge)
+                )
+            });
+            let flash_on =
+                (now.duration_since(started).as_millis() / FLASH_MILLIS).is_multiple_of(2);
+            let notice = banner
+                .as_deref()
+                .or(status.as_deref())
+                .or(feed.error())
+                .or(record_error.as_deref())
+                .or(hook_error.as_deref());
+            let main = &traces.main;
             let metrics = RenderMetrics {
-                load,
-                phase,
-                pulse,
+                name: feed.name(),
+                unit: feed.unit(),
+                raw: sample.raw,
+                detail: sample.detail.as_deref(),
+                flatline: sample.flatline,
+                rhythm: synth.rhythm,
+                load: sample.value,
+                model: synth.model,
+                renderer,
+                display,
+                plot,
+                replay: matches!(feed, Feed::Replay(_)),
+                view,
+                phase: main.osc.phase,
+                pulse: main.osc.pulse,
+                bpm: main.osc.beat.bpm(),
+                beat: main.osc.beat.progress(),
+                heart_rate: match synth.rhythm {
+                    Rhythm::Tachycardia => synth.template.tachycardia().bpm(sample.value),
+                    _ => synth.template.bpm(sample.value),
+                },
+                load_summary: traces.load_window.summary(),
                 fps,
-                phase_delta,
+                phase_rate: oscillation_rate(sample.value),
+                status: notice,
+                flash: flash_on && alarms.pending().is_some_and(|pending| !pending.recovered),
+            };
+            let target = match deadline {
+                None => Target::Screen { full_clear },
+                Some(deadline) if now >= deadline => Target::Text {
+                    color: stdout.is_terminal(),
+                },
+                Some(_) => continue,
             };
-            render(&mut stdout, &samples, metrics, full_clear)?;
+            render(
+                &mut stdout,
+                (width, height),
+                traces.leads(),
+                traces.overlay(),
+                metrics,
+                &settings,
+                target,
+            )?;
+            if let Target::Text { .. } = target {
+                break;
+            }
         }
-
-        tick = tick.saturating_add(1);
     }
 
-    execute!(stdout, Show, LeaveAlternateScreen)?;
-    terminal::disable_raw_mode()?;
     Ok(())
 }
@@ -0,0 +1,101 @@
+//! Readouts for the side panel: the heart rate in big block digits and load
+//! statistics over the last minute.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+pub const DIGIT_ROWS: usize = 5;
+/// Font pixels per glyph row; each pixel is drawn two cells wide so digits
+/// keep a rough square aspect on a terminal grid.
+const DIGIT_COLS: usize = 3;
+const PIXEL_WIDTH: usize = 2;
+const DIGIT_GAP: usize = 1;
+/// How much load history the statistics cover.
+const STATS_WINDOW: Duration = Duration::from_secs(60);
+
+/// 3x5 glyphs for `0`-`9` and `-`, `#` marking a lit pixel.
+const GLYPHS: [[&str; DIGIT_ROWS]; 11] = [
+    ["###", "# #", "# #", "# #", "###"],
+    [" # ", "## ", " # ", " # ", "###"],
+    ["###", "  #", "###", "#  ", "###"],
+    ["###", "  #", "###", "  #", "###"],
+    ["# #", "# #", "###", "  #", "  #"],
+    ["###", "#  ", "###", "  #", "###"],
+    ["###", "#  ", "###", "# #", "###"],
+    ["###", "  #", "  #", "  #", "  #"],
+    ["###", "# #", "###", "# #", "###"],
+    ["###", "# #", "###", "  #", "###"],
+    ["   ", "   ", "###", "   ", "   "],
+];
+
+/// Width in cells of `len` big characters.
+pub const fn big_width(len: usize) -> usize {
+    len * (DIGIT_COLS * PIXEL_WIDTH + DIGIT_GAP) - DIGIT_GAP
+}
+
+/// Renders digits and dashes as rows of `pixel`; other characters are blank.
+pub fn big_text(text: &str, pixel: char) -> [String; DIGIT_ROWS] {
+    std::array::from_fn(|row| {
+        let glyph_rows = text.chars().map(|ch| {
+            let glyph = match ch {
+                '0'..='9' => GLYPHS.get(ch as usize - '0' as usize),
+                '-' => GLYPHS.last(),
+                _ => None,
+            };
+            glyph.map_or("   ", |glyph| glyph[row])
+        });
+        let cells: Vec<String> = glyph_rows
+            .map(|line| {
+                line.chars()
+                    .flat_map(|lit| {
+                        let ch = if lit == '#' { pixel } else { ' ' };
+                        std::iter::repeat_n(ch, PIXEL_WIDTH)
+                    })
+                    .collect()
+            })
+            .collect();
+        cells.join(&" ".repeat(DIGIT_GAP))
+    })
+}
+
+pub struct LoadSummary {
+    pub min: f32,
+    pub max: f32,
+    pub average: f32,
+}
+
+/// Load levels from the last minute.
+pub struct LoadWindow {
+    levels: VecDeque<(Instant, f32)>,
+}
+
+impl LoadWindow {
+    pub fn new() -> Self {
+        Self {
+            levels: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, now: Instant, level: f32) {
+        while self
+            .levels
+            .front()
+            .is_some_and(|&(at, _)| now.duration_since(at) > STATS_WINDOW)
+        {
+            self.levels.pop_front();
+        }
+        self.levels.push_back((now, level));
+    }
+
+    pub fn summary(&self) -> Option<LoadSummary> {
+        if self.levels.is_empty() {
+            return None;
+        }
+        let levels = self.levels.iter().map(|&(_, level)| level);
+        Some(LoadSummary {
+            min: levels.clone().fold(f32::INFINITY, f32::min),
+            max: levels.clone().fold(f32::NEG_INFINITY, f32::max),
+            average: levels.sum::<f32>() / self.levels.len() as f32,
+        })
+    }
+}
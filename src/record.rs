@@ -0,0 +1,283 @@
+//! Session recording.
+//!
+//! Every trace sample is handed to a writer thread over a channel, so a slow
+//! disk can never hold up a frame. The file format follows the extension:
+//! `.csv` for comma-separated values, `.jsonl`/`.ndjson` for JSON Lines and
+//! `.edf` for EDF+ and `.hea` for a WFDB record. Each format is a
+//! [`SampleSink`].
+
+mod edf;
+mod wfdb;
+
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const CSV_HEADER: &str = "time,source,raw,level,value,phase,pulse,bpm,beat";
+
+const SECS_PER_DAY: u64 = 86_400;
+
+/// One trace sample and the waveform state that produced it.
+pub struct Record {
+    pub time: SystemTime,
+    pub source: String,
+    pub raw: f64,
+    pub level: f32,
+    pub value: f32,
+    pub phase: f32,
+    pub pulse: f32,
+    pub bpm: f32,
+    pub beat: f32,
+    /// Event the waveform produced at this sample.
+    pub mark: Option<Mark>,
+}
+
+#[derive(Clone, Copy)]
+pub enum Mark {
+    /// The R peak of a PQRST beat.
+    Beat,
+    /// A pulse, from the sine model or from a source event.
+    Pulse,
+}
+
+/// A file format recordings can be written in. Sinks run on the writer
+/// thread, so they may block on the disk.
+pub trait SampleSink: Send {
+    fn write(&mut self, record: &Record) -> io::Result<()>;
+    /// Called once after the last record, before the file is closed.
+    fn finish(&mut self) -> io::Result<()>;
+}
+
+/// Opens the sink matching the extension of `path`.
+fn open_sink(path: &Path) -> Result<Box<dyn SampleSink>, String> {
+    let fail = |err: io::Error| format!("{}: {err}", path.display());
+    let create = || File::create(path).map(BufWriter::new).map_err(fail);
+    let sink: Box<dyn SampleSink> = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("csv") => Box::new(TextSink::new(create()?, TextFormat::Csv).map_err(fail)?),
+        Some("jsonl" | "ndjson") => {
+            Box::new(TextSink::new(create()?, TextFormat::JsonLines).map_err(fail)?)
+        }
+        Some("edf") => Box::new(edf::EdfSink::new(create()?)),
+        Some("hea") => Box::new(wfdb::WfdbSink::create(path).map_err(fail)?),
+        _ => {
+            return Err(format!(
+                "{}: expected a .csv, .jsonl, .ndjson, .edf or .hea file",
+                path.display()
+            ));
+        }
+    };
+    Ok(sink)
+}
+
+pub struct Recorder {
+    tx: Option<Sender<Record>>,
+    writer: Option<JoinHandle<()>>,
+    error: Arc<Mutex<Option<String>>>,
+}
+
+impl Recorder {
+    /// Creates `path` and starts the writer thread.
+    pub fn create(path: &Path) -> Result<Self, String> {
+        let mut sink = open_sink(path)?;
+        let (tx, rx) = mpsc::channel::<Record>();
+        let error = Arc::new(Mutex::new(None));
+        let thread_error = Arc::clone(&error);
+        let name = path.display().to_string();
+        let writer = thread::spawn(move || {
+            let mut result = Ok(());
+            for record in rx {
+                result = sink.write(&record);
+                if result.is_err() {
+                    break;
+                }
+            }
+            if let Err(err) = result.and_then(|()| sink.finish()) {
+                *thread_error.lock().unwrap_or_else(|e| e.into_inner()) =
+                    Some(format!("recording to {name} stopped: {err}"));
+            }
+        });
+        Ok(Self {
+            tx: Some(tx),
+            writer: Some(writer),
+            error,
+        })
+    }
+
+    pub fn push(&self, record: Record) {
+        if let Some(tx) = &self.tx {
+            let _ = tx.send(record);
+        }
+    }
+
+    /// Message for a failed write; recording stops after the first one.
+    pub fn error(&self) -> Option<String> {
+        self.error.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+impl Drop for Recorder {
+    /// Lets the writer drain the queue and close the file.
+    fn drop(&mut self) {
+        self.tx = None;
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum TextFormat {
+    Csv,
+    JsonLines,
+}
+
+/// One line per record, as CSV with a header row or as JSON Lines.
+struct TextSink {
+    out: BufWriter<File>,
+    format: TextFormat,
+}
+
+impl TextSink {
+    fn new(mut out: BufWriter<File>, format: TextFormat) -> io::Result<Self> {
+        if let TextFormat::Csv = format {
+            writeln!(out, "{CSV_HEADER}")?;
+        }
+        Ok(Self { out, format })
+    }
+}
+
+impl SampleSink for TextSink {
+    fn write(&mut self, record: &Record) -> io::Result<()> {
+        let time = iso_timestamp(record.time);
+        match self.format {
+            TextFormat::Csv => writeln!(
+                self.out,
+                "{time},{},{:.3},{:.4},{:.4},{:.4},{:.4},{:.1},{:.4}",
+                csv_field(&record.source),
+                record.raw,
+                record.level,
+                record.value,
+                record.phase,
+                record.pulse,
+                record.bpm,
+                record.beat
+            ),
+            TextFormat::JsonLines => writeln!(
+                self.out,
+                "{{\"time\":\"{time}\",\"source\":{},\"raw\":{:.3},\"level\":{:.4},\
+                 \"value\":{:.4},\"phase\":{:.4},\"pulse\":{:.4},\"bpm\":{:.1},\"beat\":{:.4}}}",
+                json_string(&record.source),
+                record.raw,
+                record.level,
+                record.value,
+                record.phase,
+                record.pulse,
+                record.bpm,
+                record.beat
+            ),
+        }
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Quotes `text` for a CSV field when it holds a comma or quote, doubling any
+/// quotes inside. Line breaks become spaces to keep one record per line.
+fn csv_field(text: &str) -> Cow<'_, str> {
+    let text = if text.contains(char::is_control) {
+        Cow::Owned(text.replace(char::is_control, " "))
+    } else {
+        Cow::Borrowed(text)
+    };
+    if text.contains([',', '"']) {
+        Cow::Owned(format!("\"{}\"", text.replace('"', "\"\"")))
+    } else {
+        text
+    }
+}
+
+/// `text` as a quoted JSON string.
+fn json_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for ch in text.chars() {
+        match ch {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            ch if ch.is_control() => quoted.push_str(&format!("\\u{:04x}", u32::from(ch))),
+            ch => quoted.push(ch),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Formats `time` as an RFC 3339 UTC timestamp with millisecond precision.
+fn iso_timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((secs / SECS_PER_DAY) as i64);
+    let of_day = secs % SECS_PER_DAY;
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        of_day / 3600,
+        of_day / 60 % 60,
+        of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+/// Year, month and day of a count of days since 1970-01-01, using Howard
+/// Hinnant's `civil_from_days` algorithm.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Inverse of [`civil_from_days`].
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = i64::from((month + 9) % 12);
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Parses a UTC timestamp in the form written by [`iso_timestamp`] into
+/// seconds since the Unix epoch. The fraction is optional.
+pub fn parse_timestamp(value: &str) -> Option<f64> {
+    let (date, time) = value.strip_suffix('Z')?.split_once('T')?;
+    let mut date = date.splitn(3, '-');
+    let year: i64 = date.next()?.parse().ok()?;
+    let month: u32 = date.next()?.parse().ok()?;
+    let day: u32 = date.next()?.parse().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let mut time = time.splitn(3, ':');
+    let hours: u32 = time.next()?.parse().ok()?;
+    let minutes: u32 = time.next()?.parse().ok()?;
+    let seconds: f64 = time.next()?.parse().ok()?;
+    let days = days_from_civil(year, month, day);
+    Some(days as f64 * SECS_PER_DAY as f64 + f64::from(hours * 3600 + minutes * 60) + seconds)
+}
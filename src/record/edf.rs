@@ -0,0 +1,328 @@
+//! EDF+ (European Data Format) recordings, readable by biosignal viewers
+//! such as EDFbrowser.
+//!
+//! The file holds one-second data records with the synthesized trace, the
+//! sampled level and the timekeeping annotation channel EDF+ requires. The
+//! record count in the header is unknown until the recording ends, so it is
+//! written as `-1` and patched when the sink finishes.
+
+use super::{civil_from_days, Record, SampleSink, SECS_PER_DAY};
+use crate::{SAMPLE_RATE_HZ, SIGNAL_MAX, SIGNAL_MIN};
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::time::UNIX_EPOCH;
+
+const SAMPLES_PER_RECORD: usize = SAMPLE_RATE_HZ as usize;
+const RECORD_SECS: u32 = 1;
+const DIGITAL_MIN: i16 = i16::MIN;
+const DIGITAL_MAX: i16 = i16::MAX;
+/// 16-bit words reserved per record for the annotation channel; the
+/// timestamp of a day-long recording needs far fewer.
+const ANNOTATION_WORDS: usize = 30;
+/// Byte offset of the "number of data records" header field.
+const RECORD_COUNT_OFFSET: u64 = 236;
+const MONTHS: [&str; 12] = [
+    "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+];
+
+struct Signal {
+    label: &'static str,
+    transducer: &'static str,
+    dimension: &'static str,
+    physical_min: f32,
+    physical_max: f32,
+    samples: usize,
+}
+
+const SIGNALS: [Signal; 3] = [
+    Signal {
+        label: "ECG",
+        transducer: "ecg-cpu synthetic trace",
+        dimension: "mV",
+        physical_min: SIGNAL_MIN,
+        physical_max: SIGNAL_MAX,
+        samples: SAMPLES_PER_RECORD,
+    },
+    Signal {
+        label: "Load",
+        transducer: "ecg-cpu sampled level",
+        dimension: "%",
+        physical_min: 0.0,
+        physical_max: 100.0,
+        samples: SAMPLES_PER_RECORD,
+    },
+    Signal {
+        label: "EDF Annotations",
+        transducer: "",
+        dimension: "",
+        physical_min: -1.0,
+        physical_max: 1.0,
+        samples: ANNOTATION_WORDS,
+    },
+];
+
+pub struct EdfSink {
+    out: BufWriter<File>,
+    started: bool,
+    records: u64,
+    values: Vec<f32>,
+    levels: Vec<f32>,
+}
+
+impl EdfSink {
+    pub fn new(out: BufWriter<File>) -> Self {
+        Self {
+            out,
+            started: false,
+            records: 0,
+            values: Vec::with_capacity(SAMPLES_PER_RECORD),
+            levels: Vec::with_capacity(SAMPLES_PER_RECORD),
+        }
+    }
+
+    fn write_header(&mut self, record: &Record) -> io::Result<()> {
+        let secs = record
+            .time
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let (year, month, day) = civil_from_days((secs / SECS_PER_DAY) as i64);
+        let of_day = secs % SECS_PER_DAY;
+        let month_name = MONTHS[(month - 1) as usize];
+
+        let mut header = String::new();
+        field(&mut header, "0", 8);
+        field(&mut header, "X X X X", 80);
+        field(
+            &mut header,
+            &format!("Startdate {day:02}-{month_name}-{year} X X ecg-cpu"),
+            80,
+        );
+        field(
+            &mut header,
+            &format!("{day:02}.{month:02}.{:02}", year % 100),
+            8,
+        );
+        field(
+            &mut header,
+            &format!(
+                "{:02}.{:02}.{:02}",
+                of_day / 3600,
+                of_day / 60 % 60,
+                of_day % 60
+            ),
+            8,
+        );
+        field(&mut header, &(256 * (SIGNALS.len() + 1)).to_string(), 8);
+        field(&mut header, "EDF+C", 44);
+        field(&mut header, "-1", 8);
+        field(&mut header, &RECORD_SECS.to_string(), 8);
+        field(&mut header, &SIGNALS.len().to_string(), 4);
+        for signal in &SIGNALS {
+            field(&mut header, signal.label, 16);
+        }
+        for signal in &SIGNALS {
+            field(&mut header, signal.transducer, 80);
+        }
+        for signal in &SIGNALS {
+            field(&mut header, signal.dimension, 8);
+        }
+        for signal in &SIGNALS {
+            field(&mut header, &signal.physical_min.to_string(), 8);
+        }
+        for signal in &SIGNALS {
+            field(&mut header, &signal.physical_max.to_string(), 8);
+        }
+        for _ in &SIGNALS {
+            field(&mut header, &DIGITAL_MIN.to_string(), 8);
+        }
+        for _ in &SIGNALS {
+            field(&mut header, &DIGITAL_MAX.to_string(), 8);
+        }
+        for _ in &SIGNALS {
+            field(&mut header, "", 80);
+        }
+        for signal in &SIGNALS {
+            field(&mut header, &signal.samples.to_string(), 8);
+        }
+        for _ in &SIGNALS {
+            field(&mut header, "", 32);
+        }
+        self.out.write_all(header.as_bytes())
+    }
+
+    /// Writes the buffered samples as one data record, repeating the last
+    /// sample to fill a partial record.
+    fn write_record(&mut self) -> io::Result<()> {
+        for (signal, buffer) in SIGNALS.iter().zip([&mut self.values, &mut self.levels]) {
+            let fill = buffer.last().copied().unwrap_or(0.0);
+            buffer.resize(SAMPLES_PER_RECORD, fill);
+            for &value in buffer.iter() {
+                let digital = to_digital(value, signal);
+                self.out.write_all(&digital.to_le_bytes())?;
+            }
+            buffer.clear();
+        }
+
+        // Timekeeping annotation: "+<onset>" followed by two 0x14 and a 0x00.
+        let mut annotation =
+            format!("+{}\x14\x14\0", self.records * u64::from(RECORD_SECS)).into_bytes();
+        annotation.resize(ANNOTATION_WORDS * 2, 0);
+        self.out.write_all(&annotation)?;
+        self.records += 1;
+        Ok(())
+    }
+}
+
+impl SampleSink for EdfSink {
+    fn write(&mut self, record: &Record) -> io::Result<()> {
+        if !self.started {
+            self.write_header(record)?;
+            self.started = true;
+        }
+        self.values.push(record.value);
+        self.levels.push(record.level * 100.0);
+        if self.values.len() == SAMPLES_PER_RECORD {
+            self.write_record()?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        if !self.started {
+            return self.out.flush();
+        }
+        if !self.values.is_empty() {
+            self.write_record()?;
+        }
+        self.out.seek(SeekFrom::Start(RECORD_COUNT_OFFSET))?;
+        let mut count = String::new();
+        field(&mut count, &self.records.to_string(), 8);
+        self.out.write_all(count.as_bytes())?;
+        self.out.flush()
+    }
+}
+
+/// Appends `value` left-aligned and space-padded to `width` ASCII bytes.
+fn field(header: &mut String, value: &str, width: usize) {
+    let value: String = value.chars().filter(char::is_ascii).take(width).collect();
+    header.push_str(&format!("{value:<width$}"));
+}
+
+fn to_digital(value: f32, signal: &Signal) -> i16 {
+    let span = signal.physical_max - signal.physical_min;
+    let fraction = ((value - signal.physical_min) / span).clamp(0.0, 1.0);
+    let digital_span = f32::from(DIGITAL_MAX) - f32::from(DIGITAL_MIN);
+    (f32::from(DIGITAL_MIN) + fraction * digital_span).round() as i16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::{Duration, SystemTime};
+
+    /// Records `samples` samples into a temporary file and returns its bytes.
+    fn record(name: &str, samples: usize) -> Vec<u8> {
+        let path =
+            std::env::temp_dir().join(format!("ecg-cpu-edf-{}-{name}.edf", std::process::id()));
+        let mut sink = EdfSink::new(BufWriter::new(File::create(&path).unwrap()));
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        for index in 0..samples {
+            let record = Record {
+                time: start + Duration::from_secs_f32(index as f32 / SAMPLE_RATE_HZ),
+                source: "CPU".to_string(),
+                raw: 50.0,
+                level: 0.5,
+                value: if index % 2 == 0 { 0.5 } else { -0.5 },
+                phase: 0.0,
+                pulse: 0.0,
+                bpm: 60.0,
+                beat: 0.0,
+                mark: None,
+            };
+            sink.write(&record).unwrap();
+        }
+        sink.finish().unwrap();
+        drop(sink);
+        let bytes = fs::read(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        bytes
+    }
+
+    fn text(bytes: &[u8], offset: usize, width: usize) -> &str {
+        std::str::from_utf8(&bytes[offset..offset + width])
+            .unwrap()
+            .trim_end()
+    }
+
+    fn number<T: std::str::FromStr>(bytes: &[u8], offset: usize, width: usize) -> T {
+        text(bytes, offset, width).parse().ok().unwrap()
+    }
+
+    /// Values of one per-signal header field, which starts `before` bytes
+    /// per signal into the signal header and is `width` bytes per signal.
+    fn signal_field(bytes: &[u8], ns: usize, before: usize, width: usize) -> Vec<&str> {
+        (0..ns)
+            .map(|signal| text(bytes, 256 + ns * before + signal * width, width))
+            .collect()
+    }
+
+    #[test]
+    fn header_reads_back() {
+        // Three and a half seconds: four records, the last one padded.
+        let bytes = record("header", SAMPLES_PER_RECORD * 7 / 2);
+        let ns: usize = number(&bytes, 252, 4);
+        assert_eq!(ns, SIGNALS.len());
+        assert_eq!(number::<usize>(&bytes, 184, 8), 256 * (ns + 1));
+        assert_eq!(text(&bytes, 192, 5), "EDF+C");
+        let records: usize = number(&bytes, RECORD_COUNT_OFFSET as usize, 8);
+        assert_eq!(records, 4);
+        assert_eq!(text(&bytes, 168, 8), "14.11.23");
+        assert_eq!(text(&bytes, 176, 8), "22.13.20");
+
+        let labels = signal_field(&bytes, ns, 0, 16);
+        assert_eq!(labels, ["ECG", "Load", "EDF Annotations"]);
+        let physical_min = signal_field(&bytes, ns, 16 + 80 + 8, 8);
+        let physical_max = signal_field(&bytes, ns, 16 + 80 + 8 + 8, 8);
+        let digital_min = signal_field(&bytes, ns, 16 + 80 + 8 + 16, 8);
+        let digital_max = signal_field(&bytes, ns, 16 + 80 + 8 + 24, 8);
+        let samples = signal_field(&bytes, ns, 16 + 80 + 8 + 32 + 80, 8);
+        for (index, signal) in SIGNALS.iter().enumerate() {
+            assert_eq!(physical_min[index].parse::<f32>(), Ok(signal.physical_min));
+            assert_eq!(physical_max[index].parse::<f32>(), Ok(signal.physical_max));
+            assert_eq!(digital_min[index].parse::<i16>(), Ok(DIGITAL_MIN));
+            assert_eq!(digital_max[index].parse::<i16>(), Ok(DIGITAL_MAX));
+            assert_eq!(samples[index].parse::<usize>(), Ok(signal.samples));
+        }
+
+        let record_bytes: usize = SIGNALS.iter().map(|signal| signal.samples * 2).sum();
+        assert_eq!(bytes.len(), 256 * (ns + 1) + records * record_bytes);
+    }
+
+    #[test]
+    fn samples_read_back() {
+        let bytes = record("samples", SAMPLES_PER_RECORD * 2);
+        let header = 256 * (SIGNALS.len() + 1);
+        let digital = |offset: usize| i16::from_le_bytes([bytes[offset], bytes[offset + 1]]);
+        let physical = |digital: i16, signal: &Signal| {
+            let fraction = (f32::from(digital) - f32::from(DIGITAL_MIN))
+                / (f32::from(DIGITAL_MAX) - f32::from(DIGITAL_MIN));
+            signal.physical_min + fraction * (signal.physical_max - signal.physical_min)
+        };
+        let ecg = physical(digital(header), &SIGNALS[0]);
+        let next = physical(digital(header + 2), &SIGNALS[0]);
+        let load = physical(digital(header + SAMPLES_PER_RECORD * 2), &SIGNALS[1]);
+        assert!((ecg - 0.5).abs() < 1e-3);
+        assert!((next + 0.5).abs() < 1e-3);
+        assert!((load - 50.0).abs() < 1e-2);
+
+        let annotations = header + SAMPLES_PER_RECORD * 4;
+        assert_eq!(&bytes[annotations..annotations + 5], b"+0\x14\x14\0");
+    }
+
+    #[test]
+    fn empty_recording_writes_nothing() {
+        assert!(record("empty", 0).is_empty());
+    }
+}
@@ -0,0 +1,300 @@
+//! WFDB records for PhysioNet tools.
+//!
+//! Recording to `name.hea` writes three files side by side: the `.dat`
+//! signal file in format 16 (interleaved little-endian 16-bit samples), the
+//! `.atr` annotation file in MIT format with one annotation per beat or
+//! pulse, and the `.hea` header itself. The header needs the sample count
+//! and checksums, so it is written when the recording ends.
+
+use super::{civil_from_days, Mark, Record, SampleSink, SECS_PER_DAY};
+use crate::SAMPLE_RATE_HZ;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+/// Units per mV of the synthesized trace, which spans -1 to 1.
+const ECG_GAIN: f32 = 1000.0;
+/// Units per percent of the sampled level.
+const LEVEL_GAIN: f32 = 100.0;
+const ADC_RESOLUTION: u32 = 16;
+
+/// MIT annotation codes.
+const NORMAL: u16 = 1;
+const PACE: u16 = 12;
+const SKIP: u16 = 59;
+/// Largest sample gap an annotation word can hold.
+const MAX_DELTA: u64 = 0x3ff;
+
+struct Channel {
+    description: &'static str,
+    units: &'static str,
+    gain: f32,
+    first: i16,
+    checksum: u16,
+}
+
+impl Channel {
+    const fn new(description: &'static str, units: &'static str, gain: f32) -> Self {
+        Self {
+            description,
+            units,
+            gain,
+            first: 0,
+            checksum: 0,
+        }
+    }
+
+    fn push(&mut self, value: f32, index: u64) -> i16 {
+        let adu = (value * self.gain)
+            .round()
+            .clamp(f32::from(i16::MIN), f32::from(i16::MAX)) as i16;
+        if index == 0 {
+            self.first = adu;
+        }
+        self.checksum = self.checksum.wrapping_add(adu as u16);
+        adu
+    }
+}
+
+pub struct WfdbSink {
+    name: String,
+    header: BufWriter<File>,
+    dat: BufWriter<File>,
+    atr: BufWriter<File>,
+    channels: [Channel; 2],
+    samples: u64,
+    last_mark: u64,
+    start: Option<(String, String)>,
+}
+
+impl WfdbSink {
+    /// Creates `<name>.hea`, `<name>.dat` and `<name>.atr` for a header path
+    /// of `<name>.hea`.
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let name = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .filter(|stem| !stem.is_empty())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid record name"))?
+            .to_string();
+        let create = |extension| File::create(path.with_extension(extension)).map(BufWriter::new);
+        Ok(Self {
+            header: create("hea")?,
+            dat: create("dat")?,
+            atr: create("atr")?,
+            name,
+            channels: [
+                Channel::new("ECG", "mV", ECG_GAIN),
+                Channel::new("Load", "%", LEVEL_GAIN),
+            ],
+            samples: 0,
+            last_mark: 0,
+            start: None,
+        })
+    }
+
+    fn write_mark(&mut self, code: u16) -> io::Result<()> {
+        let delta = self.samples - self.last_mark;
+        if delta > MAX_DELTA {
+            // A skip word carries the gap as a PDP-11 long: the high half
+            // first, each half little-endian.
+            let delta = u32::try_from(delta).unwrap_or(u32::MAX);
+            self.atr.write_all(&(SKIP << 10).to_le_bytes())?;
+            self.atr.write_all(&((delta >> 16) as u16).to_le_bytes())?;
+            self.atr.write_all(&(delta as u16).to_le_bytes())?;
+            self.atr.write_all(&(code << 10).to_le_bytes())?;
+        } else {
+            self.atr
+                .write_all(&((code << 10) | delta as u16).to_le_bytes())?;
+        }
+        self.last_mark = self.samples;
+        Ok(())
+    }
+}
+
+impl SampleSink for WfdbSink {
+    fn write(&mut self, record: &Record) -> io::Result<()> {
+        if self.start.is_none() {
+            self.start = Some(base_time(record));
+        }
+        if let Some(mark) = record.mark {
+            self.write_mark(match mark {
+                Mark::Beat => NORMAL,
+                Mark::Pulse => PACE,
+            })?;
+        }
+        let values = [record.value, record.level * 100.0];
+        for (channel, value) in self.channels.iter_mut().zip(values) {
+            let adu = channel.push(value, self.samples);
+            self.dat.write_all(&adu.to_le_bytes())?;
+        }
+        self.samples += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.dat.flush()?;
+        self.atr.write_all(&[0, 0])?;
+        self.atr.flush()?;
+
+        let mut line = format!(
+            "{} {} {} {}",
+            self.name,
+            self.channels.len(),
+            SAMPLE_RATE_HZ,
+            self.samples
+        );
+        if let Some((time, date)) = &self.start {
+            line.push_str(&format!(" {time} {date}"));
+        }
+        writeln!(self.header, "{line}")?;
+        for channel in &self.channels {
+            writeln!(
+                self.header,
+                "{}.dat 16 {}/{} {ADC_RESOLUTION} 0 {} {} 0 {}",
+                self.name,
+                channel.gain,
+                channel.units,
+                channel.first,
+                channel.checksum as i16,
+                channel.description
+            )?;
+        }
+        self.header.flush()
+    }
+}
+
+/// Base time and date of the record as `HH:MM:SS` and `DD/MM/YYYY`, in UTC.
+fn base_time(record: &Record) -> (String, String) {
+    let secs = record
+        .time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (year, month, day) = civil_from_days((secs / SECS_PER_DAY) as i64);
+    let of_day = secs % SECS_PER_DAY;
+    (
+        format!(
+            "{:02}:{:02}:{:02}",
+            of_day / 3600,
+            of_day / 60 % 60,
+            of_day % 60
+        ),
+        format!("{day:02}/{month:02}/{year}"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::{Duration, SystemTime};
+
+    /// Longer than an annotation word can hold, so it needs a skip.
+    const LONG_GAP: u64 = 2000;
+    const SAMPLES: u64 = 12 + LONG_GAP + 10;
+
+    /// Records `SAMPLES` samples with a beat at 5, a pulse at 12 and another
+    /// beat `LONG_GAP` samples later, and returns the three files.
+    fn record(name: &str) -> (String, Vec<u8>, Vec<u8>) {
+        let path =
+            std::env::temp_dir().join(format!("ecg-cpu-wfdb-{}-{name}.hea", std::process::id()));
+        let mut sink = WfdbSink::create(&path).unwrap();
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        for index in 0..SAMPLES {
+            let mark = match index {
+                5 => Some(Mark::Beat),
+                12 => Some(Mark::Pulse),
+                index if index == 12 + LONG_GAP => Some(Mark::Beat),
+                _ => None,
+            };
+            let record = Record {
+                time: start + Duration::from_secs_f32(index as f32 / SAMPLE_RATE_HZ),
+                source: "CPU".to_string(),
+                raw: 50.0,
+                level: 0.5,
+                value: if index % 2 == 0 { 0.5 } else { -0.25 },
+                phase: 0.0,
+                pulse: 0.0,
+                bpm: 60.0,
+                beat: 0.0,
+                mark,
+            };
+            sink.write(&record).unwrap();
+        }
+        sink.finish().unwrap();
+        drop(sink);
+        let read = |extension| {
+            let path = path.with_extension(extension);
+            let bytes = fs::read(&path).unwrap();
+            let _ = fs::remove_file(&path);
+            bytes
+        };
+        let header = String::from_utf8(read("hea")).unwrap();
+        (header, read("dat"), read("atr"))
+    }
+
+    /// Sample index and code of every annotation in an MIT annotation file.
+    fn annotations(atr: &[u8]) -> Vec<(u64, u16)> {
+        let mut words = atr
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]));
+        let mut time = 0;
+        let mut found = Vec::new();
+        while let Some(word) = words.next() {
+            let (code, delta) = (word >> 10, u64::from(word & 0x3ff));
+            match code {
+                0 if delta == 0 => break,
+                SKIP => {
+                    let high = u64::from(words.next().unwrap());
+                    let low = u64::from(words.next().unwrap());
+                    time += high << 16 | low;
+                }
+                code => {
+                    time += delta;
+                    found.push((time, code));
+                }
+            }
+        }
+        found
+    }
+
+    #[test]
+    fn annotations_read_back() {
+        let (_, _, atr) = record("atr");
+        assert_eq!(
+            annotations(&atr),
+            [(5, NORMAL), (12, PACE), (12 + LONG_GAP, NORMAL)]
+        );
+        assert_eq!(&atr[atr.len() - 2..], [0, 0]);
+    }
+
+    #[test]
+    fn header_matches_signal_file() {
+        let (header, dat, _) = record("header");
+        let mut lines = header.lines();
+        let record_line: Vec<&str> = lines.next().unwrap().split(' ').collect();
+        assert_eq!(record_line[1..4], ["2", "30", &SAMPLES.to_string()]);
+        assert_eq!(record_line[4..], ["22:13:20", "14/11/2023"]);
+        assert_eq!(dat.len() as u64, SAMPLES * 2 * 2);
+
+        let adus: Vec<i16> = dat
+            .chunks_exact(2)
+            .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        let signals: Vec<&str> = lines.collect();
+        assert_eq!(signals.len(), 2);
+        for (signal, line) in signals.iter().enumerate() {
+            let fields: Vec<&str> = line.split(' ').collect();
+            assert_eq!(fields[1], "16");
+            let values: Vec<i16> = adus.iter().skip(signal).step_by(2).copied().collect();
+            let checksum = values
+                .iter()
+                .fold(0i16, |sum, &value| sum.wrapping_add(value));
+            assert_eq!(fields[5], values[0].to_string());
+            assert_eq!(fields[6], checksum.to_string());
+        }
+        assert_eq!(adus[..4], [500, 5000, -250, 5000]);
+    }
+}
@@ -0,0 +1,364 @@
+//! Replaying recorded sessions.
+//!
+//! A replay file is loaded up front and played back on its own clock, which
+//! can be paused, sped up or slowed down and moved around with seeks. Files
+//! written by `--record` keep their timestamps and levels; a plain file with
+//! one number per line is spread over the sampling interval and scaled to
+//! the plot.
+
+use crate::record;
+use crate::source::Sample;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const SPEED_MIN: f64 = 0.25;
+const SPEED_MAX: f64 = 16.0;
+const SPEED_STEP: f64 = 2.0;
+pub const SEEK_STEP_SECS: f64 = 5.0;
+const PERCENT_MAX: f64 = 100.0;
+const SECS_PER_MIN: u64 = 60;
+
+struct Point {
+    /// Seconds since the first point.
+    at: f64,
+    raw: f64,
+    level: f32,
+}
+
+/// One parsed line before the defaults for missing columns are filled in.
+struct Row {
+    time: Option<f64>,
+    raw: Option<f64>,
+    level: Option<f32>,
+}
+
+impl Row {
+    fn parse(time: Option<&str>, raw: Option<&str>, level: Option<&str>) -> Result<Self, String> {
+        Ok(Self {
+            time: time.map(parse_time).transpose()?,
+            raw: raw.map(parse_number).transpose()?,
+            level: level
+                .map(parse_number)
+                .transpose()?
+                .map(|level| level as f32),
+        })
+    }
+}
+
+pub struct Replay {
+    name: String,
+    points: Vec<Point>,
+    position: f64,
+    speed: f64,
+    paused: bool,
+    clock: Option<Instant>,
+}
+
+impl Replay {
+    /// Loads `path`. Rows without a timestamp are placed `interval` apart.
+    pub fn open(path: &Path, interval: Duration) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("{}: {err}", path.display()))?;
+        let (name, rows) = parse(&text).map_err(|err| format!("{}: {err}", path.display()))?;
+        if rows.is_empty() {
+            return Err(format!("{}: no samples", path.display()));
+        }
+        Ok(Self {
+            name: name.unwrap_or_else(|| "Replay".to_string()),
+            points: into_points(rows, interval.as_secs_f64()),
+            position: 0.0,
+            speed: 1.0,
+            paused: false,
+            clock: None,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn duration(&self) -> f64 {
+        self.points.last().map_or(0.0, |point| point.at)
+    }
+
+    /// Pauses or resumes; resuming at the end starts over.
+    pub fn toggle_pause(&mut self) {
+        if self.paused && self.position >= self.duration() {
+            self.position = 0.0;
+        }
+        self.paused = !self.paused;
+    }
+
+    pub fn faster(&mut self) {
+        self.speed = (self.speed * SPEED_STEP).min(SPEED_MAX);
+    }
+
+    pub fn slower(&mut self) {
+        self.speed = (self.speed / SPEED_STEP).max(SPEED_MIN);
+    }
+
+    pub fn seek(&mut self, secs: f64) {
+        self.position = (self.position + secs).clamp(0.0, self.duration());
+    }
+
+    /// Moves the replay clock on to `now` and returns the sample there.
+    pub fn sample_at(&mut self, now: Instant) -> Sample {
+        let elapsed = self
+            .clock
+            .map_or(0.0, |clock| now.duration_since(clock).as_secs_f64());
+        self.clock = Some(now);
+        if !self.paused {
+            self.position += elapsed * self.speed;
+            if self.position >= self.duration() {
+                self.position = self.duration();
+                self.paused = true;
+            }
+        }
+
+        let next = self
+            .points
+            .partition_point(|point| point.at <= self.position)
+            .min(self.points.len() - 1);
+        let from = &self.points[next.saturating_sub(1)];
+        let to = &self.points[next];
+        let span = to.at - from.at;
+        let t = if span > 0.0 {
+            ((self.position - from.at) / span).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+        let level = from.level + (to.level - from.level) * t as f32;
+        let mut sample = Sample::new(level, from.raw + (to.raw - from.raw) * t);
+        let mut detail = format!(
+            "replay {}/{}  x{}",
+            clock_time(self.position),
+            clock_time(self.duration()),
+            self.speed
+        );
+        if self.paused {
+            detail.push_str("  paused");
+        }
+        sample.detail = Some(detail);
+        sample
+    }
+}
+
+fn clock_time(secs: f64) -> String {
+    let secs = secs as u64;
+    format!("{:02}:{:02}", secs / SECS_PER_MIN, secs % SECS_PER_MIN)
+}
+
+/// Reads JSON Lines, CSV with a header row, or one number per line. Returns
+/// the recorded source name when the file has one.
+fn parse(text: &str) -> Result<(Option<String>, Vec<Row>), String> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty());
+    let Some((_, first)) = lines.clone().next() else {
+        return Ok((None, Vec::new()));
+    };
+
+    let mut name = None;
+    let mut rows = Vec::new();
+    if first.starts_with('{') {
+        for (number, line) in lines {
+            let field = |key| json_field(line, key);
+            name = name.or_else(|| field("source"));
+            let row = Row::parse(
+                field("time").as_deref(),
+                field("raw").as_deref(),
+                field("level").as_deref(),
+            );
+            rows.push(row.map_err(|err| format!("line {number}: {err}"))?);
+        }
+        return Ok((name, rows));
+    }
+
+    let columns: Vec<&str> = first.split(',').map(str::trim).collect();
+    let has_header = columns.iter().any(|column| column.parse::<f64>().is_err());
+    let index = |name: &str| columns.iter().position(|column| *column == name);
+    let (time, raw, level, source) = if has_header {
+        lines.next();
+        let raw = index("raw").or_else(|| (columns.len() == 1).then_some(0));
+        if raw.is_none() && index("level").is_none() {
+            return Err("expected a 'raw' or 'level' column".to_string());
+        }
+        (index("time"), raw, index("level"), index("source"))
+    } else {
+        (None, Some(0), None, None)
+    };
+    for (number, line) in lines {
+        let fields = csv_fields(line);
+        let field = |column: Option<usize>| {
+            column.and_then(|column| fields.get(column).map(String::as_str))
+        };
+        name = name.or_else(|| field(source).map(str::to_string));
+        let row = Row::parse(field(time), field(raw), field(level));
+        rows.push(row.map_err(|err| format!("line {number}: {err}"))?);
+    }
+    Ok((name, rows))
+}
+
+/// Fills in missing timestamps and levels. Levels are derived from the raw
+/// values: kept as they are when they all fit in `0..=1`, read as percent when
+/// they fit in `0..=100`, and scaled to the largest value otherwise.
+fn into_points(rows: Vec<Row>, interval: f64) -> Vec<Point> {
+    let peak = rows
+        .iter()
+        .filter_map(|row| row.raw)
+        .fold(0.0f64, |peak, raw| peak.max(raw.abs()));
+    let scale = if peak <= 1.0 {
+        1.0
+    } else if peak <= PERCENT_MAX {
+        PERCENT_MAX
+    } else {
+        peak
+    };
+    let start = rows.iter().find_map(|row| row.time);
+    let mut points: Vec<Point> = rows
+        .into_iter()
+        .enumerate()
+        .map(|(index, row)| {
+            let raw = row.raw.unwrap_or(0.0);
+            Point {
+                at: match (row.time, start) {
+                    (Some(time), Some(start)) => time - start,
+                    _ => index as f64 * interval,
+                },
+                raw,
+                level: row.level.unwrap_or((raw / scale) as f32).clamp(0.0, 1.0),
+            }
+        })
+        .collect();
+    points.sort_by(|a, b| a.at.total_cmp(&b.at));
+    points
+}
+
+fn parse_number(value: &str) -> Result<f64, String> {
+    value
+        .parse()
+        .ok()
+        .filter(|number: &f64| number.is_finite())
+        .ok_or_else(|| format!("'{value}' is not a number"))
+}
+
+/// Accepts an RFC 3339 UTC timestamp as written by `--record`, or seconds.
+fn parse_time(value: &str) -> Result<f64, String> {
+    record::parse_timestamp(value)
+        .or_else(|| value.parse().ok())
+        .ok_or_else(|| format!("'{value}' is not a timestamp"))
+}
+
+/// The value of `key` in a flat JSON object, with strings unquoted and
+/// unescaped.
+fn json_field(line: &str, key: &str) -> Option<String> {
+    let start = line.find(&format!("\"{key}\":"))? + key.len() + 3;
+    let rest = line[start..].trim_start();
+    let Some(quoted) = rest.strip_prefix('"') else {
+        let end = rest.find([',', '}']).unwrap_or(rest.len());
+        return Some(rest[..end].trim().to_string());
+    };
+    let mut value = String::new();
+    let mut chars = quoted.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '"' => return Some(value),
+            '\\' => match chars.next()? {
+                'n' => value.push('\n'),
+                'r' => value.push('\r'),
+                't' => value.push('\t'),
+                'u' => {
+                    let code: String = chars.by_ref().take(4).collect();
+                    let code = u32::from_str_radix(&code, 16).ok()?;
+                    value.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                }
+                escaped => value.push(escaped),
+            },
+            ch => value.push(ch),
+        }
+    }
+    None
+}
+
+/// The fields of a CSV line, trimmed, with quoted fields unquoted and their
+/// doubled quotes undone.
+fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field).trim().to_string()),
+            ch => field.push(ch),
+        }
+    }
+    fields.push(field.trim().to_string());
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record::{Record, Recorder};
+    use std::time::SystemTime;
+
+    const AWKWARD_NAME: &str = "a, \"quoted\" \\ name";
+
+    /// Records two samples under `source` to a temporary `.{extension}` file
+    /// and loads it back.
+    fn round_trip(name: &str, extension: &str, source: &str) -> Replay {
+        let path = std::env::temp_dir().join(format!(
+            "ecg-cpu-replay-{}-{name}.{extension}",
+            std::process::id()
+        ));
+        let recorder = Recorder::create(&path).unwrap();
+        for raw in [25.0, 75.0] {
+            recorder.push(Record {
+                time: SystemTime::now(),
+                source: source.to_string(),
+                raw,
+                level: (raw / PERCENT_MAX) as f32,
+                value: 0.0,
+                phase: 0.0,
+                pulse: 0.0,
+                bpm: 60.0,
+                beat: 0.0,
+                mark: None,
+            });
+        }
+        drop(recorder);
+        let replay = Replay::open(&path, Duration::from_millis(250));
+        let _ = fs::remove_file(&path);
+        replay.unwrap()
+    }
+
+    #[test]
+    fn csv_source_name_reads_back() {
+        let replay = round_trip("csv", "csv", AWKWARD_NAME);
+        assert_eq!(replay.name(), AWKWARD_NAME);
+        assert_eq!(replay.points.len(), 2);
+        assert_eq!(replay.points[1].raw, 75.0);
+    }
+
+    #[test]
+    fn json_lines_source_name_reads_back() {
+        let replay = round_trip("jsonl", "jsonl", AWKWARD_NAME);
+        assert_eq!(replay.name(), AWKWARD_NAME);
+        assert_eq!(replay.points.len(), 2);
+        assert_eq!(replay.points[1].raw, 75.0);
+    }
+
+    #[test]
+    fn csv_line_breaks_in_names_become_spaces() {
+        let replay = round_trip("lines", "csv", "two\nlines");
+        assert_eq!(replay.name(), "two lines");
+    }
+}
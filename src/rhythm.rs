@@ -0,0 +1,312 @@
+//! Arrhythmia detection.
+//!
+//! Abnormal load patterns are mapped onto heart rhythms: a sudden spike is a
+//! premature beat, erratic readings are fibrillation, a load stuck at the top
+//! is tachycardia, and a failed reading, or a zero one from a source that is
+//! never idle at zero, is asystole. Each detector
+//! looks at a short window of recent levels; none of them needs a terminal.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// How much history the spike and fibrillation detectors look at.
+const WINDOW: Duration = Duration::from_secs(3);
+/// Rise above the window average that counts as a spike.
+const SPIKE_DELTA: f32 = 0.3;
+/// How long the header names a premature beat after the spike.
+const PREMATURE_LABEL: Duration = Duration::from_secs(2);
+/// Spread of the window, and turns in its direction, that count as erratic.
+const FIBRILLATION_STDDEV: f32 = 0.12;
+const FIBRILLATION_TURNS: usize = 4;
+/// Changes smaller than this are noise when counting turns.
+const TURN_EPSILON: f32 = 0.01;
+/// Level, and how long it has to be held, that counts as a plateau.
+const TACHYCARDIA_LEVEL: f32 = 0.98;
+const TACHYCARDIA_HOLD: Duration = Duration::from_secs(5);
+/// How long a zero reading lasts before it counts as asystole.
+const ASYSTOLE_HOLD: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Rhythm {
+    Sinus,
+    Premature,
+    Tachycardia,
+    Fibrillation,
+    Asystole,
+}
+
+impl Rhythm {
+    /// Header label; a normal rhythm has none.
+    pub fn label(self) -> Option<&'static str> {
+        match self {
+            Rhythm::Sinus => None,
+            Rhythm::Premature => Some("PREMATURE BEAT"),
+            Rhythm::Tachycardia => Some("TACHYCARDIA"),
+            Rhythm::Fibrillation => Some("FIBRILLATION"),
+            Rhythm::Asystole => Some("ASYSTOLE"),
+        }
+    }
+}
+
+/// What the detectors make of the latest level.
+pub struct Assessment {
+    pub rhythm: Rhythm,
+    /// A spike just started, so a premature beat should fire now.
+    pub premature: bool,
+}
+
+pub struct Detector {
+    /// Whether a run of zero readings counts as asystole.
+    zero_is_failure: bool,
+    window: VecDeque<(Instant, f32)>,
+    /// Set while the level is still above the spike that fired, so one spike
+    /// fires one premature beat.
+    spiking: bool,
+    last_spike: Option<Instant>,
+    plateau_since: Option<Instant>,
+    zero_since: Option<Instant>,
+}
+
+impl Detector {
+    pub fn new(zero_is_failure: bool) -> Self {
+        Self {
+            zero_is_failure,
+            window: VecDeque::new(),
+            spiking: false,
+            last_spike: None,
+            plateau_since: None,
+            zero_since: None,
+        }
+    }
+
+    /// Feeds the level at `now`. `failed` marks a reading that did not come
+    /// through, such as a sampling error or a source with nothing to measure.
+    pub fn update(&mut self, now: Instant, level: f32, failed: bool) -> Assessment {
+        while self
+            .window
+            .front()
+            .is_some_and(|&(at, _)| now.duration_since(at) > WINDOW)
+        {
+            self.window.pop_front();
+        }
+
+        let rise = average(&self.window).map_or(0.0, |average| level - average);
+        let premature = !self.spiking && rise > SPIKE_DELTA;
+        if premature {
+            self.spiking = true;
+            self.last_spike = Some(now);
+        } else if rise < SPIKE_DELTA / 2.0 {
+            self.spiking = false;
+        }
+        self.window.push_back((now, level));
+
+        let plateau = held(&mut self.plateau_since, level >= TACHYCARDIA_LEVEL, now);
+        let zero = held(
+            &mut self.zero_since,
+            self.zero_is_failure && level <= 0.0,
+            now,
+        );
+
+        let rhythm = if failed || zero >= Some(ASYSTOLE_HOLD) {
+            Rhythm::Asystole
+        } else if erratic(&self.window) {
+            Rhythm::Fibrillation
+        } else if plateau >= Some(TACHYCARDIA_HOLD) {
+            Rhythm::Tachycardia
+        } else if self
+            .last_spike
+            .is_some_and(|spike| now.duration_since(spike) < PREMATURE_LABEL)
+        {
+            Rhythm::Premature
+        } else {
+            Rhythm::Sinus
+        };
+        Assessment { rhythm, premature }
+    }
+}
+
+/// The rhythm before a source's first reading, when the detectors have
+/// nothing to go on: asystole once reading has already failed.
+pub fn unmeasured(failed: bool) -> Rhythm {
+    if failed {
+        Rhythm::Asystole
+    } else {
+        Rhythm::Sinus
+    }
+}
+
+/// How long `condition` has held, tracking its start in `since`.
+fn held(since: &mut Option<Instant>, condition: bool, now: Instant) -> Option<Duration> {
+    if !condition {
+        *since = None;
+        return None;
+    }
+    Some(now.duration_since(*since.get_or_insert(now)))
+}
+
+fn average(window: &VecDeque<(Instant, f32)>) -> Option<f32> {
+    if window.is_empty() {
+        return None;
+    }
+    Some(window.iter().map(|&(_, level)| level).sum::<f32>() / window.len() as f32)
+}
+
+/// True when the window both spreads widely and keeps changing direction; a
+/// single step up or down spreads it too but never turns.
+fn erratic(window: &VecDeque<(Instant, f32)>) -> bool {
+    let Some(average) = average(window) else {
+        return false;
+    };
+    let variance = window
+        .iter()
+        .map(|&(_, level)| (level - average).powi(2))
+        .sum::<f32>()
+        / window.len() as f32;
+    if variance.sqrt() < FIBRILLATION_STDDEV {
+        return false;
+    }
+
+    let mut turns = 0;
+    let mut rising = None;
+    let mut last = window[0].1;
+    for &(_, level) in window.iter().skip(1) {
+        if (level - last).abs() < TURN_EPSILON {
+            continue;
+        }
+        let up = level > last;
+        if rising.is_some_and(|rising| rising != up) {
+            turns += 1;
+        }
+        rising = Some(up);
+        last = level;
+    }
+    turns >= FIBRILLATION_TURNS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEP: Duration = Duration::from_millis(250);
+
+    /// Feeds `levels` one reading interval apart and returns every
+    /// assessment.
+    fn feed(detector: &mut Detector, start: Instant, levels: &[f32]) -> Vec<Assessment> {
+        levels
+            .iter()
+            .enumerate()
+            .map(|(index, &level)| detector.update(start + STEP * index as u32, level, false))
+            .collect()
+    }
+
+    fn rhythms(assessments: &[Assessment]) -> Vec<Rhythm> {
+        assessments
+            .iter()
+            .map(|assessment| assessment.rhythm)
+            .collect()
+    }
+
+    #[test]
+    fn steady_load_is_sinus() {
+        let mut detector = Detector::new(true);
+        let assessments = feed(&mut detector, Instant::now(), &[0.3; 40]);
+        assert!(rhythms(&assessments)
+            .iter()
+            .all(|&rhythm| rhythm == Rhythm::Sinus));
+        assert!(assessments.iter().all(|assessment| !assessment.premature));
+    }
+
+    #[test]
+    fn spike_fires_one_premature_beat() {
+        let mut detector = Detector::new(true);
+        let mut levels = vec![0.1; 12];
+        levels.extend([0.6, 0.6, 0.6]);
+        let assessments = feed(&mut detector, Instant::now(), &levels);
+        let fired: Vec<usize> = assessments
+            .iter()
+            .enumerate()
+            .filter(|(_, assessment)| assessment.premature)
+            .map(|(index, _)| index)
+            .collect();
+        assert_eq!(fired, [12]);
+        assert!(assessments[12].rhythm == Rhythm::Premature);
+        assert!(assessments[14].rhythm == Rhythm::Premature);
+    }
+
+    #[test]
+    fn premature_label_fades() {
+        let mut detector = Detector::new(true);
+        let start = Instant::now();
+        feed(&mut detector, start, &[0.1, 0.1, 0.1, 0.6]);
+        let later = detector.update(start + PREMATURE_LABEL + STEP * 4, 0.6, false);
+        assert!(later.rhythm == Rhythm::Sinus);
+    }
+
+    #[test]
+    fn erratic_load_is_fibrillation() {
+        let mut detector = Detector::new(true);
+        let levels: Vec<f32> = (0..12)
+            .map(|index| if index % 2 == 0 { 0.2 } else { 0.6 })
+            .collect();
+        let assessments = feed(&mut detector, Instant::now(), &levels);
+        assert!(assessments.last().unwrap().rhythm == Rhythm::Fibrillation);
+    }
+
+    #[test]
+    fn single_step_is_not_fibrillation() {
+        let mut detector = Detector::new(true);
+        let mut levels = vec![0.2; 6];
+        levels.extend([0.7; 6]);
+        let assessments = feed(&mut detector, Instant::now(), &levels);
+        assert!(rhythms(&assessments)
+            .iter()
+            .all(|&rhythm| rhythm != Rhythm::Fibrillation));
+    }
+
+    #[test]
+    fn plateau_becomes_tachycardia() {
+        let mut detector = Detector::new(true);
+        let start = Instant::now();
+        let early = detector.update(start, 1.0, false);
+        assert!(early.rhythm == Rhythm::Sinus);
+        let before = detector.update(start + TACHYCARDIA_HOLD - STEP, 1.0, false);
+        assert!(before.rhythm == Rhythm::Sinus);
+        let held = detector.update(start + TACHYCARDIA_HOLD, 0.99, false);
+        assert!(held.rhythm == Rhythm::Tachycardia);
+        let dropped = detector.update(start + TACHYCARDIA_HOLD + STEP, 0.9, false);
+        assert!(dropped.rhythm == Rhythm::Sinus);
+    }
+
+    #[test]
+    fn zero_becomes_asystole_when_zero_is_a_failure() {
+        let mut detector = Detector::new(true);
+        let start = Instant::now();
+        assert!(detector.update(start, 0.0, false).rhythm == Rhythm::Sinus);
+        let held = detector.update(start + ASYSTOLE_HOLD, 0.0, false);
+        assert!(held.rhythm == Rhythm::Asystole);
+        let back = detector.update(start + ASYSTOLE_HOLD + STEP, 0.1, false);
+        assert!(back.rhythm == Rhythm::Sinus);
+    }
+
+    #[test]
+    fn zero_is_healthy_for_other_sources() {
+        let mut detector = Detector::new(false);
+        let start = Instant::now();
+        detector.update(start, 0.0, false);
+        let held = detector.update(start + ASYSTOLE_HOLD * 2, 0.0, false);
+        assert!(held.rhythm == Rhythm::Sinus);
+    }
+
+    #[test]
+    fn failed_reading_is_asystole_at_once() {
+        let mut detector = Detector::new(false);
+        let assessment = detector.update(Instant::now(), 0.5, true);
+        assert!(assessment.rhythm == Rhythm::Asystole);
+    }
+
+    #[test]
+    fn error_before_first_reading_is_asystole() {
+        assert!(unmeasured(true) == Rhythm::Asystole);
+        assert!(unmeasured(false) == Rhythm::Sinus);
+    }
+}
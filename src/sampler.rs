@@ -0,0 +1,200 @@
+//! Background sampling.
+//!
+//! A source may need a minimum gap between reads to produce meaningful numbers
+//! (CPU usage does), and a slow source must not hold up drawing. A dedicated
+//! thread samples on its own interval and publishes readings over a channel;
+//! the render loop interpolates between the last two so the trace stays
+//! smooth at any frame rate. Opening a source can take a while too, so that
+//! happens on the sampling thread as well.
+
+use crate::source::{Channel, MetricSource, Sample};
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// What the sampling thread publishes: the source's description once it is
+/// open, then one reading per interval.
+enum Message {
+    Opened {
+        name: String,
+        unit: String,
+        zero_is_failure: bool,
+    },
+    Reading(Instant, io::Result<Sample>),
+}
+
+struct Reading {
+    at: Instant,
+    sample: Sample,
+}
+
+pub struct Sampler {
+    /// Empty until the source is open.
+    name: String,
+    unit: String,
+    zero_is_failure: bool,
+    /// Set once the source is open, until [`Sampler::take_opened`].
+    opened: bool,
+    rx: Receiver<Message>,
+    interval_ms: Arc<AtomicU64>,
+    prev: Option<Reading>,
+    latest: Option<Reading>,
+    error: Option<String>,
+    /// Events from readings taken by [`Sampler::poll`] but not yet drawn.
+    events: u32,
+}
+
+impl Sampler {
+    /// Opens a source with `open` and samples it, both on a new thread. The
+    /// thread stops on its own once the returned sampler is dropped.
+    pub fn spawn<F>(open: F, interval: Duration) -> Self
+    where
+        F: FnOnce() -> Box<dyn MetricSource> + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel();
+        let interval_ms = Arc::new(AtomicU64::new(interval.as_millis() as u64));
+        let thread_interval = Arc::clone(&interval_ms);
+        thread::spawn(move || {
+            let mut source = open();
+            let opened = Message::Opened {
+                name: source.name().to_string(),
+                unit: source.unit().to_string(),
+                zero_is_failure: source.zero_is_failure(),
+            };
+            if tx.send(opened).is_err() {
+                return;
+            }
+            loop {
+                let at = Instant::now();
+                if tx.send(Message::Reading(at, source.sample())).is_err() {
+                    break;
+                }
+                let interval = thread_interval.load(Ordering::Relaxed);
+                thread::sleep(Duration::from_millis(interval));
+            }
+        });
+        Self {
+            name: String::new(),
+            unit: String::new(),
+            zero_is_failure: false,
+            opened: false,
+            rx,
+            interval_ms,
+            prev: None,
+            latest: None,
+            error: None,
+            events: 0,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn unit(&self) -> &str {
+        &self.unit
+    }
+
+    pub fn zero_is_failure(&self) -> bool {
+        self.zero_is_failure
+    }
+
+    /// True once after the source has been opened and its name, unit and
+    /// [`Sampler::zero_is_failure`] are known.
+    pub fn take_opened(&mut self) -> bool {
+        std::mem::take(&mut self.opened)
+    }
+
+    /// Message for the last failed read, cleared by the next good one.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    pub fn set_interval(&self, interval: Duration) {
+        self.interval_ms
+            .store(interval.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// Takes every reading published since the last call. Failed reads keep
+    /// the last good readings in place.
+    pub fn poll(&mut self) {
+        while let Ok(message) = self.rx.try_recv() {
+            match message {
+                Message::Opened {
+                    name,
+                    unit,
+                    zero_is_failure,
+                } => {
+                    self.name = name;
+                    self.unit = unit;
+                    self.zero_is_failure = zero_is_failure;
+                    self.opened = true;
+                }
+                Message::Reading(at, Ok(sample)) => {
+                    self.error = None;
+                    self.events = self.events.saturating_add(sample.events);
+                    self.prev = self.latest.replace(Reading { at, sample });
+                }
+                Message::Reading(_, Err(err)) => self.error = Some(format!("{}: {err}", self.name)),
+            }
+        }
+    }
+
+    /// Returns the events collected since the last call. Interpolated samples
+    /// carry none, so each event is drawn exactly once.
+    pub fn take_events(&mut self) -> u32 {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Position between the previous and latest reading, one interval behind
+    /// real time so there is always a reading to move towards.
+    fn blend(&self, now: Instant) -> f32 {
+        let (Some(prev), Some(latest)) = (&self.prev, &self.latest) else {
+            return 1.0;
+        };
+        let span = latest.at.duration_since(prev.at).as_secs_f32();
+        if span <= 0.0 {
+            return 1.0;
+        }
+        (now.duration_since(latest.at).as_secs_f32() / span).clamp(0.0, 1.0)
+    }
+
+    /// The interpolated sample for `now`, or `None` before the first reading.
+    pub fn sample_at(&self, now: Instant) -> Option<Sample> {
+        let latest = &self.latest.as_ref()?.sample;
+        let prev = self.prev.as_ref().map_or(latest, |reading| &reading.sample);
+        let t = self.blend(now);
+        let channels = if prev.channels.len() == latest.channels.len() {
+            prev.channels
+                .iter()
+                .zip(&latest.channels)
+                .map(|(from, to)| Channel {
+                    label: to.label.clone(),
+                    value: lerp(from.value, to.value, t),
+                })
+                .collect()
+        } else {
+            latest.channels.clone()
+        };
+        let overlay = match (prev.overlay, latest.overlay) {
+            (Some(from), Some(to)) => Some(lerp(from, to, t)),
+            (_, to) => to,
+        };
+        Some(Sample {
+            value: lerp(prev.value, latest.value, t),
+            raw: prev.raw + (latest.raw - prev.raw) * f64::from(t),
+            channels,
+            overlay,
+            detail: latest.detail.clone(),
+            events: 0,
+            flatline: latest.flatline,
+        })
+    }
+}
+
+fn lerp(from: f32, to: f32, t: f32) -> f32 {
+    from + (to - from) * t
+}
@@ -0,0 +1,726 @@
+//! Runtime tunables shared by the command line, the config file and the main
+//! loop.
+
+use crate::alarm::{self, AlarmRule};
+use crate::beat::{BeatTemplate, Wave};
+use crate::hook::HookConfig;
+use crossterm::style::Color;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+use sysinfo::MINIMUM_CPU_UPDATE_INTERVAL;
+
+const FPS_DEFAULT: u32 = 30;
+const FPS_MIN: u32 = 10;
+const FPS_MAX: u32 = 60;
+/// Highest frame rate whose tick is still at least a millisecond.
+const FPS_LIMIT: u32 = 1000;
+const SAMPLE_INTERVAL_MS: u64 = 250;
+
+const WARN_THRESHOLD: f32 = 0.5;
+const CRIT_THRESHOLD: f32 = 0.75;
+const PULSE_LOAD_THRESHOLD: f32 = 0.7;
+const ALARM_HYSTERESIS: f32 = 0.05;
+const HOOK_INTERVAL_SECS: u64 = 30;
+const HOOK_TIMEOUT_SECS: u64 = 10;
+
+const GRID_ROW_STEP: usize = 4;
+const GRID_COL_STEP: usize = 6;
+const ERASE_GAP: usize = 4;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Cpu,
+    Cores,
+    Memory,
+    Swap,
+    Load,
+    Net,
+    Disk,
+    Psi,
+    Cgroup,
+    Process,
+}
+
+impl Source {
+    pub const NAMES: &'static str =
+        "cpu, cores, memory, swap, load, net, disk, psi, cgroup, process";
+    const ALL: [Source; 10] = [
+        Source::Cpu,
+        Source::Cores,
+        Source::Memory,
+        Source::Swap,
+        Source::Load,
+        Source::Net,
+        Source::Disk,
+        Source::Psi,
+        Source::Cgroup,
+        Source::Process,
+    ];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "cpu" => Some(Source::Cpu),
+            "cores" => Some(Source::Cores),
+            "memory" => Some(Source::Memory),
+            "swap" => Some(Source::Swap),
+            "load" => Some(Source::Load),
+            "net" => Some(Source::Net),
+            "disk" => Some(Source::Disk),
+            "psi" => Some(Source::Psi),
+            "cgroup" => Some(Source::Cgroup),
+            "process" => Some(Source::Process),
+            _ => None,
+        }
+    }
+
+    /// The next source in [`Source::NAMES`] order, wrapping around.
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&source| source == self);
+        Self::ALL[index.map_or(0, |index| (index + 1) % Self::ALL.len())]
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum WaveModel {
+    Pqrst,
+    Sine,
+}
+
+impl WaveModel {
+    pub const NAMES: &'static str = "pqrst, sine";
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "pqrst" => Some(WaveModel::Pqrst),
+            "sine" => Some(WaveModel::Sine),
+            _ => None,
+        }
+    }
+
+    pub fn toggled(self) -> Self {
+        match self {
+            WaveModel::Pqrst => WaveModel::Sine,
+            WaveModel::Sine => WaveModel::Pqrst,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Renderer {
+    Braille,
+    Ascii,
+}
+
+impl Renderer {
+    pub const NAMES: &'static str = "braille, ascii";
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "braille" => Some(Renderer::Braille),
+            "ascii" => Some(Renderer::Ascii),
+            _ => None,
+        }
+    }
+
+    /// Braille when the locale looks like UTF-8, ASCII otherwise. Windows
+    /// terminals are assumed to handle Unicode.
+    pub fn detect() -> Self {
+        if cfg!(windows) {
+            return Renderer::Braille;
+        }
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        if locale.contains("utf-8") || locale.contains("utf8") {
+            Renderer::Braille
+        } else {
+            Renderer::Ascii
+        }
+    }
+
+    pub fn toggled(self) -> Self {
+        match self {
+            Renderer::Braille => Renderer::Ascii,
+            Renderer::Ascii => Renderer::Braille,
+        }
+    }
+}
+
+/// How new samples reach the screen: scrolling in from the right, or written
+/// by a head that sweeps left to right like a bedside monitor.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Display {
+    Scroll,
+    Sweep,
+}
+
+impl Display {
+    pub const NAMES: &'static str = "scroll, sweep";
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "scroll" => Some(Display::Scroll),
+            "sweep" => Some(Display::Sweep),
+            _ => None,
+        }
+    }
+
+    pub fn toggled(self) -> Self {
+        match self {
+            Display::Scroll => Display::Sweep,
+            Display::Sweep => Display::Scroll,
+        }
+    }
+}
+
+/// What the trace shows: the synthesized waveform, the sampled level itself
+/// on a 0-100% axis, or the waveform with the level drawn dimly behind it.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Plot {
+    Synthetic,
+    Raw,
+    Combined,
+}
+
+impl Plot {
+    pub const NAMES: &'static str = "synthetic, raw, combined";
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "synthetic" => Some(Plot::Synthetic),
+            "raw" => Some(Plot::Raw),
+            "combined" => Some(Plot::Combined),
+            _ => None,
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Plot::Synthetic => Plot::Raw,
+            Plot::Raw => Plot::Combined,
+            Plot::Combined => Plot::Synthetic,
+        }
+    }
+}
+
+/// Spacing of the background dot grid, in cells.
+#[derive(Clone, Copy)]
+pub struct Grid {
+    pub row_step: usize,
+    pub col_step: usize,
+}
+
+impl Grid {
+    /// Parses `ROWSxCOLS`, or `off` for no grid.
+    pub fn parse(value: &str) -> Result<Option<Self>, String> {
+        if value == "off" {
+            return Ok(None);
+        }
+        let invalid = || "expected ROWSxCOLS or 'off'".to_string();
+        let (rows, cols) = value.split_once('x').ok_or_else(invalid)?;
+        let row_step: usize = rows.parse().map_err(|_| invalid())?;
+        let col_step: usize = cols.parse().map_err(|_| invalid())?;
+        if row_step == 0 || col_step == 0 {
+            return Err("steps must be at least 1".to_string());
+        }
+        Ok(Some(Grid { row_step, col_step }))
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Palette {
+    pub ok: Color,
+    pub warn: Color,
+    pub crit: Color,
+    pub grid: Color,
+    pub text: Color,
+    pub overlay: Color,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            ok: Color::Green,
+            warn: Color::Yellow,
+            crit: Color::Red,
+            grid: Color::DarkGrey,
+            text: Color::White,
+            overlay: Color::DarkCyan,
+        }
+    }
+}
+
+/// Accepts crossterm color names (`dark_grey`, `red`, ...) or `#rrggbb`.
+pub fn parse_color(value: &str) -> Option<Color> {
+    if let Some(hex) = value.strip_prefix('#') {
+        if hex.len() != 6 {
+            return None;
+        }
+        let channel = |range| u8::from_str_radix(hex.get(range)?, 16).ok();
+        return Some(Color::Rgb {
+            r: channel(0..2)?,
+            g: channel(2..4)?,
+            b: channel(4..6)?,
+        });
+    }
+    Color::try_from(value).ok()
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    FpsUp,
+    FpsDown,
+    Source,
+    Model,
+    Renderer,
+    Display,
+    Plot,
+    Pause,
+    Faster,
+    Slower,
+    Acknowledge,
+    Freeze,
+}
+
+/// Keys bound to each action. Every character of a binding triggers it, so
+/// `"+="` lets both keys raise the FPS. Esc and Ctrl-C always quit.
+#[derive(Clone)]
+pub struct Keymap {
+    pub quit: String,
+    pub fps_up: String,
+    pub fps_down: String,
+    pub source: String,
+    pub model: String,
+    pub renderer: String,
+    pub display: String,
+    pub plot: String,
+    pub pause: String,
+    pub faster: String,
+    pub slower: String,
+    pub acknowledge: String,
+    pub freeze: String,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self {
+            quit: "q".to_string(),
+            fps_up: "+=".to_string(),
+            fps_down: "-_".to_string(),
+            source: "cC".to_string(),
+            model: "wW".to_string(),
+            renderer: "bB".to_string(),
+            display: "sS".to_string(),
+            plot: "pP".to_string(),
+            pause: " ".to_string(),
+            faster: "]".to_string(),
+            slower: "[".to_string(),
+            acknowledge: "aA".to_string(),
+            freeze: "fF".to_string(),
+        }
+    }
+}
+
+impl Keymap {
+    fn bindings(&self) -> [(&str, Action); 13] {
+        [
+            (&self.quit, Action::Quit),
+            (&self.fps_up, Action::FpsUp),
+            (&self.fps_down, Action::FpsDown),
+            (&self.source, Action::Source),
+            (&self.model, Action::Model),
+            (&self.renderer, Action::Renderer),
+            (&self.display, Action::Display),
+            (&self.plot, Action::Plot),
+            (&self.pause, Action::Pause),
+            (&self.faster, Action::Faster),
+            (&self.slower, Action::Slower),
+            (&self.acknowledge, Action::Acknowledge),
+            (&self.freeze, Action::Freeze),
+        ]
+    }
+
+    pub fn action(&self, key: char) -> Option<Action> {
+        self.bindings()
+            .into_iter()
+            .find(|(keys, _)| keys.contains(key))
+            .map(|(_, action)| action)
+    }
+
+    /// Primary key of an action, as shown in the footer.
+    pub fn label(&self, action: Action) -> String {
+        self.bindings()
+            .into_iter()
+            .find(|&(_, bound)| bound == action)
+            .and_then(|(keys, _)| keys.chars().next())
+            .map(|key| match key {
+                ' ' => "Space".to_string(),
+                key => key.to_uppercase().collect(),
+            })
+            .unwrap_or_default()
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let bindings = self.bindings();
+        for (index, (keys, _)) in bindings.iter().enumerate() {
+            if keys.is_empty() {
+                return Err("every key binding needs at least one key".to_string());
+            }
+            for key in keys.chars() {
+                if bindings[index + 1..]
+                    .iter()
+                    .any(|(other, _)| other.contains(key))
+                {
+                    return Err(format!("key '{key}' is bound to more than one action"));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Why [`Settings::set`] rejected an option.
+pub enum SetError {
+    Unknown,
+    Invalid(String),
+}
+
+#[derive(Clone)]
+pub struct Settings {
+    pub fps: u32,
+    pub fps_min: u32,
+    pub fps_max: u32,
+    /// Time between CPU readings, in milliseconds.
+    pub interval_ms: u64,
+    /// Load at which the trace turns yellow, as a fraction.
+    pub warn: f32,
+    /// Load at which the trace turns red, as a fraction.
+    pub crit: f32,
+    pub grid: Option<Grid>,
+    pub source: Source,
+    /// cgroup v2 directory for the cgroup source; detected when unset.
+    pub cgroup: Option<PathBuf>,
+    /// Process filters for the process source; unset filters match anything.
+    pub pid: Option<u32>,
+    pub process_name: Option<String>,
+    pub user: Option<String>,
+    pub model: WaveModel,
+    pub renderer: Renderer,
+    pub display: Display,
+    pub plot: Plot,
+    /// Width of the blank gap ahead of the sweep write head, in cells.
+    pub erase_gap: usize,
+    /// Load above which the sine model fires pulses, as a fraction.
+    pub pulse_threshold: f32,
+    pub bpm_min: f32,
+    pub bpm_max: f32,
+    /// PQRST waves of the beat template.
+    pub p_wave: Wave,
+    pub q_wave: Wave,
+    pub r_wave: Wave,
+    pub s_wave: Wave,
+    pub t_wave: Wave,
+    pub alarms: Vec<AlarmRule>,
+    /// How far, as a fraction, the load must move back past an alarm
+    /// threshold before the alarm recovers.
+    pub alarm_hysteresis: f32,
+    /// Ring the terminal bell when an alarm fires.
+    pub bell: bool,
+    /// Shell commands run when an alarm fires or recovers.
+    pub on_alarm: Option<String>,
+    pub on_recover: Option<String>,
+    /// Least time between two runs of the same hook, in seconds.
+    pub hook_interval_secs: u64,
+    /// Time after which a hook is killed, in seconds.
+    pub hook_timeout_secs: u64,
+    pub palette: Palette,
+    pub keys: Keymap,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        let template = BeatTemplate::default();
+        Self {
+            fps: FPS_DEFAULT,
+            fps_min: FPS_MIN,
+            fps_max: FPS_MAX,
+            interval_ms: SAMPLE_INTERVAL_MS,
+            warn: WARN_THRESHOLD,
+            crit: CRIT_THRESHOLD,
+            grid: Some(Grid {
+                row_step: GRID_ROW_STEP,
+                col_step: GRID_COL_STEP,
+            }),
+            source: Source::Cpu,
+            cgroup: None,
+            pid: None,
+            process_name: None,
+            user: None,
+            model: WaveModel::Pqrst,
+            renderer: Renderer::detect(),
+            display: Display::Scroll,
+            plot: Plot::Synthetic,
+            erase_gap: ERASE_GAP,
+            pulse_threshold: PULSE_LOAD_THRESHOLD,
+            bpm_min: template.bpm_min,
+            bpm_max: template.bpm_max,
+            p_wave: template.p,
+            q_wave: template.q,
+            r_wave: template.r,
+            s_wave: template.s,
+            t_wave: template.t,
+            alarms: Vec::new(),
+            alarm_hysteresis: ALARM_HYSTERESIS,
+            bell: false,
+            on_alarm: None,
+            on_recover: None,
+            hook_interval_secs: HOOK_INTERVAL_SECS,
+            hook_timeout_secs: HOOK_TIMEOUT_SECS,
+            palette: Palette::default(),
+            keys: Keymap::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// Option names accepted by [`Settings::set`].
+    pub const OPTIONS: &'static [&'static str] = &[
+        "fps",
+        "fps-min",
+        "fps-max",
+        "interval",
+        "warn",
+        "crit",
+        "grid",
+        "source",
+        "cgroup",
+        "pid",
+        "name",
+        "user",
+        "model",
+        "renderer",
+        "display",
+        "plot",
+        "erase-gap",
+        "pulse-threshold",
+        "bpm-min",
+        "bpm-max",
+        "p-wave",
+        "q-wave",
+        "r-wave",
+        "s-wave",
+        "t-wave",
+        "alarm",
+        "alarm-hysteresis",
+        "bell",
+        "on-alarm",
+        "on-recover",
+        "hook-interval",
+        "hook-timeout",
+    ];
+
+    /// Checks the relations between values that each parse fine on their own.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.fps_min == 0 {
+            return Err("fps-min must be at least 1".to_string());
+        }
+        if self.fps_max > FPS_LIMIT {
+            return Err(format!(
+                "fps-max ({}) must be at most {FPS_LIMIT}",
+                self.fps_max
+            ));
+        }
+        if self.fps_min > self.fps_max {
+            return Err(format!(
+                "fps-min ({}) is greater than fps-max ({})",
+                self.fps_min, self.fps_max
+            ));
+        }
+        if self.fps < self.fps_min || self.fps > self.fps_max {
+            return Err(format!(
+                "fps {} is outside the {}..={} range",
+                self.fps, self.fps_min, self.fps_max
+            ));
+        }
+        if self.interval() < MINIMUM_CPU_UPDATE_INTERVAL {
+            return Err(format!(
+                "interval {} ms is below the {} ms CPU readings need",
+                self.interval_ms,
+                MINIMUM_CPU_UPDATE_INTERVAL.as_millis()
+            ));
+        }
+        if self.warn >= self.crit {
+            return Err(format!(
+                "warn threshold ({:.0}%) must be below crit threshold ({:.0}%)",
+                self.warn * 100.0,
+                self.crit * 100.0
+            ));
+        }
+        if let Some(grid) = self.grid
+            && (grid.row_step == 0 || grid.col_step == 0)
+        {
+            return Err("grid steps must be at least 1".to_string());
+        }
+        if !self.bpm_min.is_finite() || !self.bpm_max.is_finite() || self.bpm_min <= 0.0 {
+            return Err("bpm-min and bpm-max must be positive numbers".to_string());
+        }
+        if self.bpm_min >= self.bpm_max {
+            return Err(format!(
+                "bpm-min ({}) must be below bpm-max ({})",
+                self.bpm_min, self.bpm_max
+            ));
+        }
+        let filtered = self.pid.is_some() || self.process_name.is_some() || self.user.is_some();
+        if filtered && self.source != Source::Process {
+            return Err("pid, name and user only apply with source process".to_string());
+        }
+        self.beat_template().validate()?;
+        if self.hook_timeout_secs == 0 {
+            return Err("hook-timeout must be at least 1 second".to_string());
+        }
+        self.keys.validate()
+    }
+
+    /// Sets one option by its command-line name (without the leading `--`).
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), SetError> {
+        match name {
+            "fps" => self.fps = parse_number(value)?,
+            "fps-min" => self.fps_min = parse_number(value)?,
+            "fps-max" => self.fps_max = parse_number(value)?,
+            "interval" => self.interval_ms = parse_number(value)?,
+            "warn" => self.warn = parse_percent(value)?,
+            "crit" => self.crit = parse_percent(value)?,
+            "grid" => self.grid = Grid::parse(value).map_err(SetError::Invalid)?,
+            "source" => {
+                self.source = Source::parse(value).ok_or_else(|| {
+                    SetError::Invalid(format!("expected one of: {}", Source::NAMES))
+                })?;
+            }
+            "cgroup" => self.cgroup = Some(PathBuf::from(parse_text(value)?)),
+            "pid" => self.pid = Some(parse_number(value)?),
+            "name" => self.process_name = Some(parse_text(value)?),
+            "user" => self.user = Some(parse_text(value)?),
+            "model" => {
+                self.model = WaveModel::parse(value).ok_or_else(|| {
+                    SetError::Invalid(format!("expected one of: {}", WaveModel::NAMES))
+                })?;
+            }
+            "renderer" => {
+                self.renderer = Renderer::parse(value).ok_or_else(|| {
+                    SetError::Invalid(format!("expected one of: {}", Renderer::NAMES))
+                })?;
+            }
+            "display" => {
+                self.display = Display::parse(value).ok_or_else(|| {
+                    SetError::Invalid(format!("expected one of: {}", Display::NAMES))
+                })?;
+            }
+            "plot" => {
+                self.plot = Plot::parse(value).ok_or_else(|| {
+                    SetError::Invalid(format!("expected one of: {}", Plot::NAMES))
+                })?;
+            }
+            "erase-gap" => self.erase_gap = parse_number(value)?,
+            "pulse-threshold" => self.pulse_threshold = parse_percent(value)?,
+            "bpm-min" => self.bpm_min = parse_number(value)?,
+            "bpm-max" => self.bpm_max = parse_number(value)?,
+            "p-wave" => self.p_wave = parse_wave(value)?,
+            "q-wave" => self.q_wave = parse_wave(value)?,
+            "r-wave" => self.r_wave = parse_wave(value)?,
+            "s-wave" => self.s_wave = parse_wave(value)?,
+            "t-wave" => self.t_wave = parse_wave(value)?,
+            "alarm" => self.alarms = alarm::parse_rules(value).map_err(SetError::Invalid)?,
+            "alarm-hysteresis" => self.alarm_hysteresis = parse_percent(value)?,
+            "bell" => self.bell = parse_switch(value)?,
+            "on-alarm" => self.on_alarm = Some(parse_text(value)?),
+            "on-recover" => self.on_recover = Some(parse_text(value)?),
+            "hook-interval" => self.hook_interval_secs = parse_number(value)?,
+            "hook-timeout" => self.hook_timeout_secs = parse_number(value)?,
+            _ => return Err(SetError::Unknown),
+        }
+        Ok(())
+    }
+
+    pub fn line_color(&self, load: f32) -> Color {
+        if load < self.warn {
+            self.palette.ok
+        } else if load < self.crit {
+            self.palette.warn
+        } else {
+            self.palette.crit
+        }
+    }
+
+    /// True when both settings open sources the same way, so a running
+    /// sampler can be kept.
+    pub fn same_source_options(&self, other: &Settings) -> bool {
+        self.cgroup == other.cgroup
+            && self.pid == other.pid
+            && self.process_name == other.process_name
+            && self.user == other.user
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::from_millis(self.interval_ms)
+    }
+
+    pub fn hooks(&self) -> HookConfig<'_> {
+        HookConfig {
+            on_alarm: self.on_alarm.as_deref(),
+            on_recover: self.on_recover.as_deref(),
+            interval: Duration::from_secs(self.hook_interval_secs),
+            timeout: Duration::from_secs(self.hook_timeout_secs),
+        }
+    }
+
+    pub fn beat_template(&self) -> BeatTemplate {
+        BeatTemplate {
+            bpm_min: self.bpm_min,
+            bpm_max: self.bpm_max,
+            p: self.p_wave,
+            q: self.q_wave,
+            r: self.r_wave,
+            s: self.s_wave,
+            t: self.t_wave,
+        }
+    }
+}
+
+fn parse_number<T: FromStr>(value: &str) -> Result<T, SetError> {
+    value
+        .parse()
+        .map_err(|_| SetError::Invalid("expected a number".to_string()))
+}
+
+fn parse_text(value: &str) -> Result<String, SetError> {
+    if value.is_empty() {
+        return Err(SetError::Invalid("must not be empty".to_string()));
+    }
+    Ok(value.to_string())
+}
+
+fn parse_wave(value: &str) -> Result<Wave, SetError> {
+    Wave::parse(value).map_err(SetError::Invalid)
+}
+
+fn parse_switch(value: &str) -> Result<bool, SetError> {
+    match value {
+        "on" | "true" => Ok(true),
+        "off" | "false" => Ok(false),
+        _ => Err(SetError::Invalid("expected on or off".to_string())),
+    }
+}
+
+/// Accepts `75` or `75%` and returns the fraction `0.75`.
+fn parse_percent(value: &str) -> Result<f32, SetError> {
+    let number: f32 = parse_number(value.trim_end_matches('%'))?;
+    if !(0.0..=100.0).contains(&number) {
+        return Err(SetError::Invalid("must be between 0 and 100".to_string()));
+    }
+    Ok(number / 100.0)
+}
@@ -0,0 +1,128 @@
+//! Signals the monitor can plot.
+//!
+//! A [`MetricSource`] is polled from the sampler thread and reports a reading
+//! normalized to `0.0..=1.0`, which drives the waveform and colors, plus the
+//! same reading in the source's own unit for the header.
+
+mod cgroup;
+mod cpu;
+mod disk;
+mod load;
+mod memory;
+mod net;
+mod process;
+mod psi;
+
+use crate::settings::{Settings, Source};
+use std::io;
+
+#[derive(Clone)]
+pub struct Channel {
+    pub label: String,
+    pub value: f32,
+}
+
+pub struct Sample {
+    /// Normalized level in `0.0..=1.0`.
+    pub value: f32,
+    /// The same reading in [`MetricSource::unit`].
+    pub raw: f64,
+    /// Per-channel levels for sources drawn as several leads.
+    pub channels: Vec<Channel>,
+    /// Level of a secondary signal drawn over the main trace.
+    pub overlay: Option<f32>,
+    /// Extra header text, such as absolute amounts behind a percentage.
+    pub detail: Option<String>,
+    /// Discrete events since the previous reading, drawn as pulses.
+    pub events: u32,
+    /// The watched thing is gone; the trace goes flat instead of beating.
+    pub flatline: bool,
+}
+
+impl Sample {
+    /// Placeholder used until the first reading arrives, drawn as a flat
+    /// baseline.
+    pub fn idle() -> Self {
+        Self {
+            flatline: true,
+            ..Self::new(0.0, 0.0)
+        }
+    }
+
+    pub fn new(value: f32, raw: f64) -> Self {
+        Self {
+            value: value.clamp(0.0, 1.0),
+            raw,
+            channels: Vec::new(),
+            overlay: None,
+            detail: None,
+            events: 0,
+            flatline: false,
+        }
+    }
+}
+
+pub trait MetricSource: Send {
+    fn name(&self) -> &str;
+    fn unit(&self) -> &str;
+    fn sample(&mut self) -> io::Result<Sample>;
+
+    /// True when a reading of exactly zero means the source is broken rather
+    /// than idle, so a run of them counts as asystole.
+    fn zero_is_failure(&self) -> bool {
+        false
+    }
+}
+
+/// Opens `source`, taking any source-specific options from `settings`.
+pub fn open(source: Source, settings: &Settings) -> Box<dyn MetricSource> {
+    match source {
+        Source::Cpu => Box::new(cpu::CpuSource::new(false)),
+        Source::Cores => Box::new(cpu::CpuSource::new(true)),
+        Source::Memory => Box::new(memory::MemorySource::new()),
+        Source::Swap => Box::new(memory::SwapSource::new()),
+        Source::Load => Box::new(load::LoadSource::new()),
+        Source::Net => Box::new(net::NetSource::new()),
+        Source::Disk => Box::new(disk::DiskSource::new()),
+        Source::Psi => Box::new(psi::PsiSource::new(psi::PRESSURE_ROOT)),
+        Source::Cgroup => Box::new(cgroup::CgroupSource::new(settings.cgroup.clone())),
+        Source::Process => Box::new(process::ProcessSource::new(process::ProcessFilter {
+            pid: settings.pid,
+            name: settings.process_name.clone(),
+            user: settings.user.clone(),
+        })),
+    }
+}
+
+const BYTES_PER_MIB: f64 = 1024.0 * 1024.0;
+/// Smallest full scale for throughput, so an idle link doesn't fill the plot.
+const THROUGHPUT_FLOOR_MIB: f64 = 1.0;
+/// Fraction of the full scale kept per second once traffic drops.
+const THROUGHPUT_SCALE_DECAY: f64 = 0.98;
+
+/// Auto-ranging full scale for byte rates, which have no natural maximum.
+/// It jumps up to any new peak and eases back down afterwards.
+struct Throughput {
+    scale: f64,
+}
+
+impl Throughput {
+    fn new() -> Self {
+        Self {
+            scale: THROUGHPUT_FLOOR_MIB,
+        }
+    }
+
+    /// Turns `bytes` moved over `secs` into a sample in MiB/s.
+    fn sample(&mut self, bytes: u64, secs: f64) -> Sample {
+        let rate = if secs > 0.0 {
+            bytes as f64 / BYTES_PER_MIB / secs
+        } else {
+            0.0
+        };
+        self.scale = (self.scale * THROUGHPUT_SCALE_DECAY.powf(secs))
+            .max(rate)
+            .max(THROUGHPUT_FLOOR_MIB);
+        Sample::new((rate / self.scale) as f32, rate)
+    }
+}
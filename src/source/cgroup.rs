@@ -0,0 +1,143 @@
+use super::{MetricSource, Sample};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+const CGROUP_MOUNT: &str = "/sys/fs/cgroup";
+/// Where hybrid v1/v2 setups mount the v2 hierarchy.
+const HYBRID_MOUNT: &str = "/sys/fs/cgroup/unified";
+const SELF_CGROUP: &str = "/proc/self/cgroup";
+const MICROS_PER_SEC: f64 = 1_000_000.0;
+
+/// The cgroup v2 directory of this process, from the `0::/path` line of
+/// `/proc/self/cgroup`.
+fn detect() -> io::Result<PathBuf> {
+    let text = fs::read_to_string(SELF_CGROUP)?;
+    let path = text
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                "no cgroup v2 hierarchy in /proc/self/cgroup",
+            )
+        })?;
+    let mount = if Path::new(CGROUP_MOUNT).join("cgroup.controllers").exists() {
+        CGROUP_MOUNT
+    } else {
+        HYBRID_MOUNT
+    };
+    Ok(Path::new(mount).join(path.trim_start_matches('/')))
+}
+
+/// Value of `key` in a flat-keyed file such as `cpu.stat`.
+fn stat_field(text: &str, key: &str) -> Option<u64> {
+    text.lines().find_map(|line| {
+        let (name, value) = line.split_once(' ')?;
+        if name == key {
+            value.trim().parse().ok()
+        } else {
+            None
+        }
+    })
+}
+
+/// CPUs worth of time the quota in `cpu.max` allows, or `None` for `max`.
+fn quota_cpus(text: &str) -> Option<f64> {
+    let mut fields = text.split_whitespace();
+    let quota: f64 = fields.next()?.parse().ok()?;
+    let period: f64 = fields.next()?.parse().ok()?;
+    (period > 0.0).then_some(quota / period)
+}
+
+struct Counters {
+    at: Instant,
+    usage_usec: u64,
+    nr_throttled: u64,
+}
+
+/// CPU use of one cgroup v2 group as a fraction of its `cpu.max` quota, or of
+/// all CPUs when the group has no quota. New throttling periods are reported
+/// as events so they show up as pulses.
+pub struct CgroupSource {
+    /// Holds the detection error when no path was given and none was found.
+    dir: io::Result<PathBuf>,
+    cpus: f64,
+    prev: Option<Counters>,
+}
+
+impl CgroupSource {
+    /// Watches `dir`, or the group this process runs in when it is `None`.
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        let cpus = std::thread::available_parallelism().map_or(1, |count| count.get());
+        Self {
+            dir: dir.map_or_else(detect, Ok),
+            cpus: cpus as f64,
+            prev: None,
+        }
+    }
+
+    fn read(dir: &Path, file: &str) -> io::Result<String> {
+        let path = dir.join(file);
+        fs::read_to_string(&path)
+            .map_err(|err| io::Error::new(err.kind(), format!("{}: {err}", path.display())))
+    }
+}
+
+impl MetricSource for CgroupSource {
+    fn name(&self) -> &str {
+        "Cgroup"
+    }
+
+    fn unit(&self) -> &str {
+        "%"
+    }
+
+    fn sample(&mut self) -> io::Result<Sample> {
+        let dir = match &self.dir {
+            Ok(dir) => dir,
+            Err(err) => return Err(io::Error::new(err.kind(), err.to_string())),
+        };
+        let stat = Self::read(dir, "cpu.stat")?;
+        let usage_usec = stat_field(&stat, "usage_usec").ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "cpu.stat has no usage_usec")
+        })?;
+        // The root group and groups without the cpu controller have no
+        // throttling counters or quota.
+        let nr_throttled = stat_field(&stat, "nr_throttled").unwrap_or(0);
+        let quota = Self::read(dir, "cpu.max")
+            .ok()
+            .and_then(|text| quota_cpus(&text));
+        let limit = quota.unwrap_or(self.cpus);
+
+        let now = Counters {
+            at: Instant::now(),
+            usage_usec,
+            nr_throttled,
+        };
+        let (usage, events) = match &self.prev {
+            Some(prev) => {
+                let secs = now.at.duration_since(prev.at).as_secs_f64();
+                let used = now.usage_usec.saturating_sub(prev.usage_usec) as f64 / MICROS_PER_SEC;
+                let usage = if secs > 0.0 && limit > 0.0 {
+                    used / secs / limit
+                } else {
+                    0.0
+                };
+                (usage, now.nr_throttled.saturating_sub(prev.nr_throttled))
+            }
+            None => (0.0, 0),
+        };
+        self.prev = Some(now);
+
+        let mut sample = Sample::new(usage as f32, usage * 100.0);
+        sample.events = u32::try_from(events).unwrap_or(u32::MAX);
+        let quota = match quota {
+            Some(cpus) => format!("quota: {cpus:.2} CPUs"),
+            None => "quota: none".to_string(),
+        };
+        sample.detail = Some(format!("{quota}  throttled: {nr_throttled}"));
+        Ok(sample)
+    }
+}
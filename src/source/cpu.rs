@@ -0,0 +1,68 @@
+use super::{Channel, MetricSource, Sample};
+use std::io;
+use std::thread;
+use std::time::Instant;
+use sysinfo::{System, MINIMUM_CPU_UPDATE_INTERVAL};
+
+pub struct CpuSource {
+    sys: System,
+    per_core: bool,
+    last_refresh: Instant,
+}
+
+impl CpuSource {
+    pub fn new(per_core: bool) -> Self {
+        let mut sys = System::new();
+        // The first refresh only establishes a baseline for the next one.
+        sys.refresh_cpu_all();
+        Self {
+            sys,
+            per_core,
+            last_refresh: Instant::now(),
+        }
+    }
+}
+
+impl MetricSource for CpuSource {
+    fn name(&self) -> &str {
+        if self.per_core {
+            "Cores"
+        } else {
+            "CPU"
+        }
+    }
+
+    fn unit(&self) -> &str {
+        "%"
+    }
+
+    /// The monitor itself always uses some CPU.
+    fn zero_is_failure(&self) -> bool {
+        true
+    }
+
+    fn sample(&mut self) -> io::Result<Sample> {
+        let since = self.last_refresh.elapsed();
+        if since < MINIMUM_CPU_UPDATE_INTERVAL {
+            thread::sleep(MINIMUM_CPU_UPDATE_INTERVAL - since);
+        }
+        self.sys.refresh_cpu_all();
+        self.last_refresh = Instant::now();
+
+        let usage = self.sys.global_cpu_usage();
+        let mut sample = Sample::new(usage / 100.0, f64::from(usage));
+        if self.per_core {
+            sample.channels = self
+                .sys
+                .cpus()
+                .iter()
+                .enumerate()
+                .map(|(index, cpu)| Channel {
+                    label: format!("c{index}"),
+                    value: (cpu.cpu_usage() / 100.0).clamp(0.0, 1.0),
+                })
+                .collect();
+        }
+        Ok(sample)
+    }
+}
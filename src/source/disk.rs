@@ -0,0 +1,50 @@
+use super::{MetricSource, Sample, Throughput};
+use std::collections::HashSet;
+use std::io;
+use std::time::Instant;
+use sysinfo::Disks;
+
+/// Bytes read plus written across all disks. A device mounted more than once
+/// is only counted once.
+pub struct DiskSource {
+    disks: Disks,
+    last_refresh: Instant,
+    throughput: Throughput,
+}
+
+impl DiskSource {
+    pub fn new() -> Self {
+        Self {
+            disks: Disks::new_with_refreshed_list(),
+            last_refresh: Instant::now(),
+            throughput: Throughput::new(),
+        }
+    }
+}
+
+impl MetricSource for DiskSource {
+    fn name(&self) -> &str {
+        "Disk"
+    }
+
+    fn unit(&self) -> &str {
+        " MiB/s"
+    }
+
+    fn sample(&mut self) -> io::Result<Sample> {
+        self.disks.refresh(true);
+        let secs = self.last_refresh.elapsed().as_secs_f64();
+        self.last_refresh = Instant::now();
+        let mut seen = HashSet::new();
+        let bytes = self
+            .disks
+            .iter()
+            .filter(|disk| seen.insert(disk.name().to_owned()))
+            .map(|disk| {
+                let usage = disk.usage();
+                usage.read_bytes + usage.written_bytes
+            })
+            .sum();
+        Ok(self.throughput.sample(bytes, secs))
+    }
+}
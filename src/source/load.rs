@@ -0,0 +1,31 @@
+use super::{MetricSource, Sample};
+use std::io;
+use sysinfo::System;
+
+/// One-minute load average, normalized by the number of CPUs so a load equal
+/// to the core count fills the plot.
+pub struct LoadSource {
+    cpus: usize,
+}
+
+impl LoadSource {
+    pub fn new() -> Self {
+        let cpus = std::thread::available_parallelism().map_or(1, |count| count.get());
+        Self { cpus }
+    }
+}
+
+impl MetricSource for LoadSource {
+    fn name(&self) -> &str {
+        "Load"
+    }
+
+    fn unit(&self) -> &str {
+        ""
+    }
+
+    fn sample(&mut self) -> io::Result<Sample> {
+        let one = System::load_average().one;
+        Ok(Sample::new((one / self.cpus as f64) as f32, one))
+    }
+}
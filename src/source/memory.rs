@@ -0,0 +1,88 @@
+use super::{MetricSource, Sample};
+use std::io;
+use sysinfo::System;
+
+const BYTES_PER_GIB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+fn gib(bytes: u64) -> f64 {
+    bytes as f64 / BYTES_PER_GIB
+}
+
+fn fraction(used: u64, total: u64) -> f32 {
+    if total == 0 {
+        0.0
+    } else {
+        (used as f64 / total as f64) as f32
+    }
+}
+
+/// Used memory as the main trace, with swap use overlaid when the machine
+/// has swap configured.
+pub struct MemorySource {
+    sys: System,
+}
+
+impl MemorySource {
+    pub fn new() -> Self {
+        Self { sys: System::new() }
+    }
+}
+
+impl MetricSource for MemorySource {
+    fn name(&self) -> &str {
+        "Memory"
+    }
+
+    fn unit(&self) -> &str {
+        "%"
+    }
+
+    fn zero_is_failure(&self) -> bool {
+        true
+    }
+
+    fn sample(&mut self) -> io::Result<Sample> {
+        self.sys.refresh_memory();
+        let (used, total) = (self.sys.used_memory(), self.sys.total_memory());
+        let level = fraction(used, total);
+        let mut sample = Sample::new(level, f64::from(level) * 100.0);
+        let mut detail = format!("{:.2}/{:.2} GiB", gib(used), gib(total));
+        let (swap_used, swap_total) = (self.sys.used_swap(), self.sys.total_swap());
+        if swap_total > 0 {
+            sample.overlay = Some(fraction(swap_used, swap_total));
+            detail.push_str(&format!(
+                "  swap: {:.2}/{:.2} GiB",
+                gib(swap_used),
+                gib(swap_total)
+            ));
+        }
+        sample.detail = Some(detail);
+        Ok(sample)
+    }
+}
+
+pub struct SwapSource {
+    sys: System,
+}
+
+impl SwapSource {
+    pub fn new() -> Self {
+        Self { sys: System::new() }
+    }
+}
+
+impl MetricSource for SwapSource {
+    fn name(&self) -> &str {
+        "Swap"
+    }
+
+    fn unit(&self) -> &str {
+        "%"
+    }
+
+    fn sample(&mut self) -> io::Result<Sample> {
+        self.sys.refresh_memory();
+        let used = fraction(self.sys.used_swap(), self.sys.total_swap());
+        Ok(Sample::new(used, f64::from(used) * 100.0))
+    }
+}
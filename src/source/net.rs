@@ -0,0 +1,43 @@
+use super::{MetricSource, Sample, Throughput};
+use std::io;
+use std::time::Instant;
+use sysinfo::Networks;
+
+/// Received plus transmitted bytes across all interfaces.
+pub struct NetSource {
+    networks: Networks,
+    last_refresh: Instant,
+    throughput: Throughput,
+}
+
+impl NetSource {
+    pub fn new() -> Self {
+        Self {
+            networks: Networks::new_with_refreshed_list(),
+            last_refresh: Instant::now(),
+            throughput: Throughput::new(),
+        }
+    }
+}
+
+impl MetricSource for NetSource {
+    fn name(&self) -> &str {
+        "Network"
+    }
+
+    fn unit(&self) -> &str {
+        " MiB/s"
+    }
+
+    fn sample(&mut self) -> io::Result<Sample> {
+        self.networks.refresh(true);
+        let secs = self.last_refresh.elapsed().as_secs_f64();
+        self.last_refresh = Instant::now();
+        let bytes = self
+            .networks
+            .values()
+            .map(|data| data.received() + data.transmitted())
+            .sum();
+        Ok(self.throughput.sample(bytes, secs))
+    }
+}
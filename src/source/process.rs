@@ -0,0 +1,145 @@
+use super::{MetricSource, Sample};
+use std::io;
+use std::thread;
+use std::time::Instant;
+use sysinfo::{
+    Pid, Process, ProcessRefreshKind, ProcessesToUpdate, System, UpdateKind, Users,
+    MINIMUM_CPU_UPDATE_INTERVAL,
+};
+
+/// Names listed in the header before the rest are summarised as a count.
+const HEADER_NAMES: usize = 3;
+
+/// Which processes to watch. Every filter that is set must match.
+pub struct ProcessFilter {
+    pub pid: Option<u32>,
+    /// Substring of the process name.
+    pub name: Option<String>,
+    /// User name or numeric user id.
+    pub user: Option<String>,
+}
+
+impl ProcessFilter {
+    fn matches(&self, process: &Process, users: &Users) -> bool {
+        if let Some(pid) = self.pid
+            && process.pid() != Pid::from_u32(pid)
+        {
+            return false;
+        }
+        if let Some(name) = &self.name
+            && !process.name().to_string_lossy().contains(name.as_str())
+        {
+            return false;
+        }
+        if let Some(user) = &self.user {
+            let Some(uid) = process.user_id() else {
+                return false;
+            };
+            let by_name = users
+                .get_user_by_id(uid)
+                .is_some_and(|owner| owner.name() == user);
+            if !by_name && uid.to_string() != *user {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Summed CPU use of the processes matching a filter, normalized by the number
+/// of CPUs. Once every matching process has exited the trace flatlines until
+/// a new match appears.
+pub struct ProcessSource {
+    sys: System,
+    users: Users,
+    filter: ProcessFilter,
+    cpus: usize,
+    last_refresh: Instant,
+    seen: bool,
+}
+
+impl ProcessSource {
+    pub fn new(filter: ProcessFilter) -> Self {
+        let mut source = Self {
+            sys: System::new(),
+            users: Users::new_with_refreshed_list(),
+            filter,
+            cpus: thread::available_parallelism().map_or(1, |count| count.get()),
+            last_refresh: Instant::now(),
+            seen: false,
+        };
+        // The first refresh only establishes a baseline for the next one.
+        source.refresh();
+        source
+    }
+
+    fn refresh(&mut self) {
+        self.sys.refresh_processes_specifics(
+            ProcessesToUpdate::All,
+            true,
+            ProcessRefreshKind::nothing()
+                .with_cpu()
+                .with_user(UpdateKind::OnlyIfNotSet),
+        );
+        self.last_refresh = Instant::now();
+    }
+}
+
+impl MetricSource for ProcessSource {
+    fn name(&self) -> &str {
+        "Process"
+    }
+
+    fn unit(&self) -> &str {
+        "%"
+    }
+
+    fn sample(&mut self) -> io::Result<Sample> {
+        let since = self.last_refresh.elapsed();
+        if since < MINIMUM_CPU_UPDATE_INTERVAL {
+            thread::sleep(MINIMUM_CPU_UPDATE_INTERVAL - since);
+        }
+        self.refresh();
+
+        let mut usage = 0.0f32;
+        let mut names: Vec<String> = Vec::new();
+        let mut count = 0;
+        for process in self.sys.processes().values() {
+            if !self.filter.matches(process, &self.users) {
+                continue;
+            }
+            count += 1;
+            usage += process.cpu_usage();
+            let name = process.name().to_string_lossy().into_owned();
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+
+        if count == 0 {
+            let mut sample = Sample::new(0.0, 0.0);
+            sample.flatline = true;
+            sample.detail = Some(if self.seen {
+                "FLATLINE: all matching processes exited".to_string()
+            } else {
+                "FLATLINE: no matching processes".to_string()
+            });
+            return Ok(sample);
+        }
+        self.seen = true;
+
+        names.sort();
+        let mut listed = names
+            .iter()
+            .take(HEADER_NAMES)
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(", ");
+        if names.len() > HEADER_NAMES {
+            listed.push_str(&format!(" +{}", names.len() - HEADER_NAMES));
+        }
+        let mut sample = Sample::new(usage / 100.0 / self.cpus as f32, f64::from(usage));
+        sample.detail = Some(format!("procs: {count}  {listed}"));
+        Ok(sample)
+    }
+}
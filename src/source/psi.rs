@@ -0,0 +1,183 @@
+use super::{MetricSource, Sample};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+pub const PRESSURE_ROOT: &str = "/proc/pressure";
+const RESOURCES: [(&str, &str); 3] = [("cpu", "cpu"), ("memory", "mem"), ("io", "io")];
+
+/// Ten-second stall averages from one `/proc/pressure` file, in percent.
+#[derive(Clone, Copy, Default)]
+struct Pressure {
+    some: f64,
+    full: f64,
+}
+
+/// Parses the `some`/`full` lines of a pressure file. Kernels before 5.13
+/// have no `full` line for CPU, which reads as zero.
+fn parse(text: &str) -> Option<Pressure> {
+    let mut pressure = Pressure::default();
+    let mut seen_some = false;
+    for line in text.lines() {
+        let mut fields = line.split_whitespace();
+        let slot = match fields.next() {
+            Some("some") => {
+                seen_some = true;
+                &mut pressure.some
+            }
+            Some("full") => &mut pressure.full,
+            _ => continue,
+        };
+        let avg10 = fields.find_map(|field| field.strip_prefix("avg10="))?;
+        *slot = avg10.parse().ok()?;
+    }
+    seen_some.then_some(pressure)
+}
+
+/// Linux Pressure Stall Information. The trace follows the worst `some`
+/// average across CPU, memory and IO, with the worst `full` average overlaid.
+pub struct PsiSource {
+    root: PathBuf,
+}
+
+impl PsiSource {
+    /// Reads `cpu`, `memory` and `io` from `root`, normally [`PRESSURE_ROOT`].
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn read(&self, resource: &str) -> io::Result<Pressure> {
+        let path = self.root.join(resource);
+        let text = fs::read_to_string(&path).map_err(|err| {
+            // Missing files mean no PSI support; with `psi=0` on the kernel
+            // command line the files exist but refuse reads.
+            if matches!(
+                err.kind(),
+                io::ErrorKind::NotFound | io::ErrorKind::Unsupported
+            ) {
+                io::Error::new(
+                    err.kind(),
+                    "pressure stall information is not available on this kernel",
+                )
+            } else {
+                io::Error::new(err.kind(), format!("{}: {err}", path.display()))
+            }
+        })?;
+        parse(&text).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: unexpected format", path.display()),
+            )
+        })
+    }
+}
+
+impl MetricSource for PsiSource {
+    fn name(&self) -> &str {
+        "Pressure"
+    }
+
+    fn unit(&self) -> &str {
+        "%"
+    }
+
+    fn sample(&mut self) -> io::Result<Sample> {
+        let mut some = 0.0f64;
+        let mut full = 0.0f64;
+        let mut detail = Vec::new();
+        for (resource, label) in RESOURCES {
+            let pressure = self.read(resource)?;
+            some = some.max(pressure.some);
+            full = full.max(pressure.full);
+            detail.push(format!(
+                "{label}: {:.1}/{:.1}",
+                pressure.some, pressure.full
+            ));
+        }
+        let mut sample = Sample::new((some / 100.0) as f32, some);
+        sample.overlay = Some((full / 100.0) as f32);
+        sample.detail = Some(detail.join("  "));
+        Ok(sample)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    const CPU: &str = "some avg10=1.50 avg60=1.00 avg300=0.50 total=12345\n\
+                       full avg10=0.30 avg60=0.10 avg300=0.00 total=678\n";
+    const CPU_BEFORE_5_13: &str = "some avg10=1.50 avg60=1.00 avg300=0.50 total=12345\n";
+    const MEMORY: &str = "some avg10=12.00 avg60=8.00 avg300=4.00 total=999\n\
+                          full avg10=3.00 avg60=2.00 avg300=1.00 total=555\n";
+    const IO: &str = "some avg10=4.00 avg60=3.00 avg300=2.00 total=111\n\
+                      full avg10=6.50 avg60=1.00 avg300=0.50 total=222\n";
+
+    /// A fresh directory standing in for `/proc/pressure`.
+    fn fixture(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("ecg-cpu-psi-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        for (file, text) in files {
+            fs::write(root.join(file), text).unwrap();
+        }
+        root
+    }
+
+    fn sample(root: &Path) -> io::Result<Sample> {
+        let result = PsiSource::new(root).sample();
+        let _ = fs::remove_dir_all(root);
+        result
+    }
+
+    #[test]
+    fn follows_the_worst_averages() {
+        let root = fixture("normal", &[("cpu", CPU), ("memory", MEMORY), ("io", IO)]);
+        let sample = sample(&root).unwrap();
+        assert_eq!(sample.raw, 12.0);
+        assert!((sample.value - 0.12).abs() < 1e-6);
+        assert!((sample.overlay.unwrap() - 0.065).abs() < 1e-6);
+        assert_eq!(
+            sample.detail.as_deref(),
+            Some("cpu: 1.5/0.3  mem: 12.0/3.0  io: 4.0/6.5")
+        );
+    }
+
+    #[test]
+    fn missing_cpu_full_line_reads_as_zero() {
+        let root = fixture(
+            "no-full",
+            &[("cpu", CPU_BEFORE_5_13), ("memory", MEMORY), ("io", IO)],
+        );
+        let sample = sample(&root).unwrap();
+        assert!(sample.detail.unwrap().starts_with("cpu: 1.5/0.0"));
+    }
+
+    #[test]
+    fn missing_directory_is_unsupported() {
+        let root = fixture("missing", &[]);
+        fs::remove_dir(&root).unwrap();
+        let err = sample(&root).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(
+            err.to_string(),
+            "pressure stall information is not available on this kernel"
+        );
+    }
+
+    #[test]
+    fn malformed_average_is_rejected() {
+        let memory = "some avg10=lots avg60=8.00 avg300=4.00 total=999\n";
+        let root = fixture("malformed", &[("cpu", CPU), ("memory", memory), ("io", IO)]);
+        let err = sample(&root).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().ends_with("memory: unexpected format"));
+    }
+
+    #[test]
+    fn parse_needs_a_some_line() {
+        assert!(parse("full avg10=1.00 avg60=0.00 avg300=0.00 total=1\n").is_none());
+        assert!(parse("some avg60=1.00\n").is_none());
+    }
+}
@@ -0,0 +1,73 @@
+//! Terminal setup and guaranteed teardown.
+//!
+//! The guard restores the terminal when it is dropped, which covers normal
+//! exits, `?` error returns and unwinding panics. The panic hook restores the
+//! terminal before the panic message is printed so it lands on the user's
+//! normal screen. Termination signals only raise a flag; the main loop checks
+//! it and returns, letting the guard do the cleanup.
+
+use crossterm::cursor::{Hide, Show};
+use crossterm::execute;
+use crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
+use std::io;
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+pub struct TerminalGuard {
+    shutdown: Arc<AtomicBool>,
+}
+
+impl TerminalGuard {
+    pub fn enter() -> io::Result<Self> {
+        let shutdown = Arc::new(AtomicBool::new(false));
+        register_signals(&shutdown)?;
+        install_panic_hook();
+
+        terminal::enable_raw_mode()?;
+        let guard = Self { shutdown };
+        execute!(io::stdout(), EnterAlternateScreen, Hide)?;
+        Ok(guard)
+    }
+
+    /// True once SIGTERM, SIGHUP, SIGINT or SIGQUIT has been received.
+    pub fn shutdown_requested(&self) -> bool {
+        self.shutdown.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore();
+    }
+}
+
+/// Leaves the alternate screen, shows the cursor and disables raw mode. Safe
+/// to call more than once.
+fn restore() {
+    let _ = execute!(io::stdout(), Show, LeaveAlternateScreen);
+    let _ = terminal::disable_raw_mode();
+}
+
+fn install_panic_hook() {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        restore();
+        previous(info);
+    }));
+}
+
+#[cfg(unix)]
+fn register_signals(shutdown: &Arc<AtomicBool>) -> io::Result<()> {
+    use signal_hook::consts::{SIGHUP, SIGINT, SIGQUIT, SIGTERM};
+
+    for signal in [SIGTERM, SIGHUP, SIGINT, SIGQUIT] {
+        signal_hook::flag::register(signal, Arc::clone(shutdown))?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn register_signals(_shutdown: &Arc<AtomicBool>) -> io::Result<()> {
+    Ok(())
+}
@@ -0,0 +1,199 @@
+//! Runs the monitor on a pseudo-terminal and checks that every way out of it
+//! hands the terminal back: cursor shown, alternate screen left and cooked
+//! mode restored.
+
+#![cfg(target_os = "linux")]
+
+use std::ffi::CStr;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd};
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const ENTER_ALTERNATE_SCREEN: &str = "\x1b[?1049h";
+const LEAVE_ALTERNATE_SCREEN: &str = "\x1b[?1049l";
+const SHOW_CURSOR: &str = "\x1b[?25h";
+const TIMEOUT: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+struct Session {
+    master: File,
+    /// Kept open so the terminal settings can still be read after the
+    /// monitor has exited.
+    slave: File,
+    child: Child,
+    output: Arc<Mutex<Vec<u8>>>,
+}
+
+impl Session {
+    fn start(envs: &[(&str, &str)]) -> Self {
+        let (master, slave) = open_pty();
+        let mut command = Command::new(env!("CARGO_BIN_EXE_ecg-cpu"));
+        command
+            .env("XDG_CONFIG_HOME", env!("CARGO_TARGET_TMPDIR"))
+            .envs(envs.iter().copied())
+            .stdin(Stdio::from(slave.try_clone().unwrap()))
+            .stdout(Stdio::from(slave.try_clone().unwrap()))
+            .stderr(Stdio::from(slave.try_clone().unwrap()));
+        // SAFETY: only async-signal-safe calls between fork and exec.
+        unsafe {
+            command.pre_exec(|| {
+                if libc::setsid() < 0 || libc::ioctl(0, libc::TIOCSCTTY, 0) < 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        let child = command.spawn().expect("failed to start ecg-cpu");
+
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let mut reader = master.try_clone().unwrap();
+        let sink = Arc::clone(&output);
+        thread::spawn(move || {
+            let mut buffer = [0; 4096];
+            // Reading fails with EIO once every slave end is closed.
+            while let Ok(read @ 1..) = reader.read(&mut buffer) {
+                sink.lock().unwrap().extend_from_slice(&buffer[..read]);
+            }
+        });
+        Self {
+            master,
+            slave,
+            child,
+            output,
+        }
+    }
+
+    fn output(&self) -> String {
+        String::from_utf8_lossy(&self.output.lock().unwrap()).into_owned()
+    }
+
+    /// Waits for the monitor to take over the terminal and checks it is in raw
+    /// mode.
+    fn wait_for_screen(&self) {
+        let deadline = Instant::now() + TIMEOUT;
+        while !self.output().contains(ENTER_ALTERNATE_SCREEN) {
+            assert!(Instant::now() < deadline, "no alternate screen in time");
+            thread::sleep(POLL_INTERVAL);
+        }
+        assert!(!self.cooked(), "raw mode was not enabled");
+    }
+
+    fn signal(&self, signal: libc::c_int) {
+        // SAFETY: plain syscall on the child's pid.
+        let result = unsafe { libc::kill(self.child.id() as libc::pid_t, signal) };
+        assert_eq!(result, 0, "kill failed");
+    }
+
+    fn wait(&mut self) -> ExitStatus {
+        let deadline = Instant::now() + TIMEOUT;
+        loop {
+            if let Some(status) = self.child.try_wait().unwrap() {
+                // Let the reader drain what was written just before exit.
+                thread::sleep(Duration::from_millis(200));
+                return status;
+            }
+            if Instant::now() >= deadline {
+                let _ = self.child.kill();
+                panic!("ecg-cpu did not exit in time");
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// True when the terminal is back in canonical mode with echo on.
+    fn cooked(&self) -> bool {
+        // SAFETY: termios is plain data and the fd is open.
+        let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+        let result = unsafe { libc::tcgetattr(self.slave.as_raw_fd(), &mut termios) };
+        assert_eq!(result, 0, "tcgetattr failed");
+        termios.c_lflag & libc::ICANON != 0 && termios.c_lflag & libc::ECHO != 0
+    }
+
+    /// Checks that the last thing done to the screen was to restore it.
+    fn assert_restored(&self) {
+        let output = self.output();
+        let entered = output
+            .rfind(ENTER_ALTERNATE_SCREEN)
+            .expect("alternate screen never entered");
+        let teardown = &output[entered..];
+        let shown = teardown
+            .find(SHOW_CURSOR)
+            .unwrap_or_else(|| panic!("cursor not shown: {teardown:?}"));
+        let left = teardown
+            .find(LEAVE_ALTERNATE_SCREEN)
+            .unwrap_or_else(|| panic!("alternate screen not left: {teardown:?}"));
+        assert!(shown < left, "cursor shown after leaving the screen");
+        assert!(self.cooked(), "terminal left in raw mode");
+    }
+}
+
+fn open_pty() -> (File, File) {
+    // SAFETY: checked libc calls; the returned fds are owned by the files.
+    unsafe {
+        let master = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+        assert!(master >= 0, "posix_openpt failed");
+        assert_eq!(libc::grantpt(master), 0, "grantpt failed");
+        assert_eq!(libc::unlockpt(master), 0, "unlockpt failed");
+        let mut name = [0 as libc::c_char; 128];
+        assert_eq!(libc::ptsname_r(master, name.as_mut_ptr(), name.len()), 0);
+        let size = libc::winsize {
+            ws_row: 24,
+            ws_col: 80,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        assert_eq!(libc::ioctl(master, libc::TIOCSWINSZ, &size), 0);
+        let slave = libc::open(
+            CStr::from_ptr(name.as_ptr()).as_ptr(),
+            libc::O_RDWR | libc::O_NOCTTY,
+        );
+        assert!(slave >= 0, "cannot open the slave end");
+        (File::from_raw_fd(master), File::from_raw_fd(slave))
+    }
+}
+
+#[test]
+fn quit_key_restores_terminal() {
+    let mut session = Session::start(&[]);
+    session.wait_for_screen();
+    session.master.write_all(b"q").unwrap();
+    assert!(session.wait().success());
+    session.assert_restored();
+}
+
+#[test]
+fn sigterm_restores_terminal() {
+    let mut session = Session::start(&[]);
+    session.wait_for_screen();
+    session.signal(libc::SIGTERM);
+    assert!(session.wait().success());
+    session.assert_restored();
+}
+
+#[test]
+fn sighup_restores_terminal() {
+    let mut session = Session::start(&[]);
+    session.wait_for_screen();
+    session.signal(libc::SIGHUP);
+    let status = session.wait();
+    assert!(status.success(), "killed by signal {:?}", status.signal());
+    session.assert_restored();
+}
+
+#[test]
+#[cfg_attr(
+    not(feature = "test-panic"),
+    ignore = "needs the panic trigger from --features test-panic"
+)]
+fn panic_restores_terminal() {
+    let mut session = Session::start(&[("ECG_CPU_TEST_PANIC", "1")]);
+    let status = session.wait();
+    assert_eq!(status.code(), Some(101), "expected a panic exit");
+    session.assert_restored();
+    assert!(session.output().contains("ECG_CPU_TEST_PANIC is set"));
+}